@@ -0,0 +1,39 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    /// Read-only queries over kitties that would otherwise need a full state scan.
+    pub trait KittiesApi<KittyIndex, Balance, ProvenanceRecord> where
+        KittyIndex: Codec,
+        Balance: Codec,
+        ProvenanceRecord: Codec,
+    {
+        /// Rarity score of a kitty, `None` if it does not exist.
+        fn rarity_of(kitty_id: KittyIndex) -> Option<u64>;
+        /// The index the next minted kitty gets.
+        fn next_kitty_id() -> KittyIndex;
+        /// Kitties ever minted, including burned ones.
+        fn total_minted() -> KittyIndex;
+        /// Kitties burned so far.
+        fn total_burned() -> KittyIndex;
+        /// Kitties currently alive.
+        fn live_supply() -> KittyIndex;
+        /// Reasons other pallets currently hold a lock on a kitty for.
+        fn locks_of(kitty_id: KittyIndex) -> Vec<[u8; 8]>;
+        /// Previous owners of a kitty, oldest first, bounded by the pallet's `MaxProvenance`.
+        fn provenance_of(kitty_id: KittyIndex) -> Vec<ProvenanceRecord>;
+        /// What a kitty last sold for in the native currency.
+        fn last_sale_price(kitty_id: KittyIndex) -> Option<Balance>;
+        /// Native-currency sale volume of the current and the previous volume window.
+        fn rolling_volume() -> Balance;
+        /// Lowest native-currency sale of a generation in the last window it sold in.
+        fn generation_floor(generation: u32) -> Option<Balance>;
+        /// Lowest native-currency sale of kitties with this dominant trait variant, in the
+        /// last window one sold in.
+        fn trait_floor(variant: u8) -> Option<Balance>;
+        /// The `n` cheapest kitties listed in the native currency, cheapest first.
+        fn cheapest_listings(n: u32) -> Vec<(KittyIndex, Balance)>;
+    }
+}
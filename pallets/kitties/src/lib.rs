@@ -1,20 +1,34 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 pub use pallet::*;
+pub use traits::{KittyLifecycleHooks, KittyLock, LockReason};
 
 #[cfg(test)]
 mod mock;
 #[cfg(test)]
 mod tests;
 
+pub mod traits;
+
 #[frame_support::pallet]
 pub mod pallet {
+    use crate::traits::{KittyLifecycleHooks, KittyLock, LockReason};
     use codec::{Decode, Encode};
     use frame_support::{
         dispatch::DispatchResult,
         pallet_prelude::*,
-        sp_runtime::traits::{AtLeast32BitUnsigned, Bounded, CheckedAdd, One},
-        traits::{tokens::ExistenceRequirement, Currency, Randomness, ReservableCurrency},
+        storage::migration::{storage_key_iter, take_storage_value},
+        sp_runtime::traits::{
+            AccountIdConversion, AtLeast32BitUnsigned, Bounded, CheckedAdd, Hash, IdentifyAccount,
+            One, UniqueSaturatedInto, Verify, Zero,
+        },
+        sp_std::{collections::btree_map::BTreeMap, prelude::*},
+        traits::{
+            tokens::{fungibles, ExistenceRequirement},
+            BalanceStatus, Currency, PalletInfoAccess, Randomness, ReservableCurrency,
+            WithdrawReasons,
+        },
+        transactional, PalletId,
     };
     use frame_system::pallet_prelude::*;
     use sp_io::hashing::blake2_128;
@@ -24,6 +38,51 @@ pub mod pallet {
     type BalanceOf<T> =
         <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
 
+    type AssetIdOf<T> = <<T as Config>::Assets as fungibles::Inspect<
+        <T as frame_system::Config>::AccountId,
+    >>::AssetId;
+
+    /// What a listed kitty is paid in: the native currency or a `pallet_assets` token.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum PaymentAsset<AssetId> {
+        Native,
+        Asset(AssetId),
+    }
+
+    impl<AssetId> Default for PaymentAsset<AssetId> {
+        fn default() -> Self {
+            PaymentAsset::Native
+        }
+    }
+
+    /// Either form of kitty identifier: the sequential index or the content id
+    /// derived from dna, creator and mint block.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum KittyRef<KittyIndex, Hash> {
+        Index(KittyIndex),
+        Hash(Hash),
+    }
+
+    pub type KittyRefOf<T> =
+        KittyRef<<T as Config>::KittyIndex, <T as frame_system::Config>::Hash>;
+
+    /// Number of trait slots a kitty carries, one per byte of dna.
+    pub const TRAIT_SLOTS: usize = 16;
+
+    /// Numerator of the per-trait rarity contribution, see `rarity_of`.
+    pub const RARITY_SCALE: u64 = 1_000_000;
+
+    /// Most listings `buy_cheapest` looks at, cheapest first, before giving up.
+    pub const MAX_CHEAPEST_SCAN: usize = 32;
+
+    /// Prefixed to a `SellOrder` before it is signed, and followed by the genesis hash, so the
+    /// signature cannot be reused for another call or on another chain.
+    pub const SELL_ORDER_CONTEXT: &[u8] = b"kitties/sell-order";
+
+    /// Prefixed to a `RelayedTransfer` before it is signed, and followed by the genesis hash like
+    /// `SELL_ORDER_CONTEXT`.
+    pub const RELAYED_TRANSFER_CONTEXT: &[u8] = b"kitties/relayed-transfer";
+
     #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
     pub struct Kitty<T: Config> {
         pub dna: [u8; 16],
@@ -31,13 +90,229 @@ pub mod pallet {
         pub price: Option<BalanceOf<T>>,
     }
 
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum Gender {
+        Male,
+        Female,
+    }
+
+    impl<T: Config> Kitty<T> {
+        // the gender is decided by the lowest bit of the first dna byte.
+        pub fn gender(&self) -> Gender {
+            if self.dna[0] % 2 == 0 {
+                Gender::Male
+            } else {
+                Gender::Female
+            }
+        }
+    }
+
+    /// A kitty lent to `borrower` until block `until`. The loan only grants usage rights once the
+    /// borrower has accepted it and paid `fee`, while the kitty stays locked from `lend` on.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct Loan<AccountId, Balance, BlockNumber> {
+        pub borrower: AccountId,
+        pub until: BlockNumber,
+        pub fee: Balance,
+        pub accepted: bool,
+    }
+
+    type LoanOf<T> = Loan<
+        <T as frame_system::Config>::AccountId,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
+
+    pub type SwapId = u32;
+
+    pub type CollectionId = u32;
+
+    /// The rules of a kitty collection, chosen by the account that created it. Collection 0 holds
+    /// the kitties of `create_kitty` and has no entry: anyone can breed and transfer them.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct CollectionConfig<AccountId, Balance> {
+        pub owner: AccountId,
+        // paid to the owner for every kitty created in the collection, on top of the mint deposit.
+        pub mint_price: Balance,
+        // the maximum number of live kitties in the collection, unlimited if None.
+        pub max_supply: Option<u32>,
+        pub breeding_enabled: bool,
+        pub transferable: bool,
+    }
+
+    type CollectionConfigOf<T> =
+        CollectionConfig<<T as frame_system::Config>::AccountId, BalanceOf<T>>;
+
+    // storage layout versions, for `on_runtime_upgrade`.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum Releases {
+        // `Kitties` keyed by `KittyIndex` only.
+        V1,
+        // `Kitties` keyed by `(CollectionId, KittyIndex)`.
+        V2,
+        // `KittiesCount` split into `NextKittyId` and the supply counters.
+        V3,
+        // `Generation` recorded for bred kitties.
+        V4,
+        // native-currency listings indexed in `ListingIndex`.
+        V5,
+    }
+
+    impl Default for Releases {
+        fn default() -> Self {
+            Releases::V1
+        }
+    }
+
+    /// The economic parameters of the pallet, adjustable by `ParametersOrigin`.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct EconomicParameters<AccountId, Balance> {
+        // the amount reserved from the owner for every minted or bred kitty.
+        pub mint_deposit: Balance,
+        // the amount charged for every `breed`, on top of the mint deposit.
+        pub breeding_fee: Balance,
+        // where breeding fees are paid to, they are burned if this is None.
+        pub fee_destination: Option<AccountId>,
+    }
+
+    /// Who may call `create_kitty`: nobody, allowlisted accounts only, or everyone.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum MintPhase {
+        Closed,
+        Presale,
+        Public,
+    }
+
+    /// The limits of the presale phase: how many kitties every allowlisted account can create,
+    /// and an optional price per kitty together with the account it is paid to.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct PresaleTerms<AccountId, Balance> {
+        pub quota: u32,
+        pub price: Option<(Balance, AccountId)>,
+    }
+
+    type PresaleTermsOf<T> = PresaleTerms<<T as frame_system::Config>::AccountId, BalanceOf<T>>;
+
+    type EconomicParametersOf<T> =
+        EconomicParameters<<T as frame_system::Config>::AccountId, BalanceOf<T>>;
+
+    /// The part of the pallet `set_paused` stops. `All` also stops the calls that belong to no
+    /// other scope, such as `burn` and cancellations.
+    #[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+    pub enum PauseScope {
+        Nothing,
+        Minting,
+        Breeding,
+        Trading,
+        All,
+    }
+
+    impl Default for PauseScope {
+        fn default() -> Self {
+            PauseScope::Nothing
+        }
+    }
+
+    /// An open offer of `offered` kitties plus an optional `sweetener` from `proposer`, in exchange
+    /// for the `requested` kitties of `counterparty`. The proposer's side is held in escrow.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct Swap<AccountId, KittyIndex, Balance> {
+        pub proposer: AccountId,
+        pub counterparty: AccountId,
+        pub offered: Vec<KittyIndex>,
+        pub requested: Vec<KittyIndex>,
+        pub sweetener: Option<Balance>,
+    }
+
+    type SwapOf<T> = Swap<
+        <T as frame_system::Config>::AccountId,
+        <T as Config>::KittyIndex,
+        BalanceOf<T>,
+    >;
+
+    pub type BundleId = u32;
+
+    /// Kitties `seller` sells together for one native-currency `price`. They stay locked until
+    /// the bundle is bought or cancelled.
+    #[derive(Encode, Decode, Clone, PartialEq, RuntimeDebug)]
+    pub struct Bundle<AccountId, KittyIndex, Balance> {
+        pub seller: AccountId,
+        pub kitties: Vec<KittyIndex>,
+        pub price: Balance,
+    }
+
+    type BundleOf<T> = Bundle<
+        <T as frame_system::Config>::AccountId,
+        <T as Config>::KittyIndex,
+        BalanceOf<T>,
+    >;
+
+    /// An offer to sell `kitty_id` for `price` in the native currency until block `expiry`,
+    /// signed off chain by the owner and filled on chain by a buyer through `fill_order`.
+    /// Every `nonce` can be used once per seller.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+    pub struct SellOrder<KittyIndex, Balance, BlockNumber> {
+        pub kitty_id: KittyIndex,
+        pub price: Balance,
+        pub expiry: BlockNumber,
+        pub nonce: u64,
+    }
+
+    pub type SellOrderOf<T> = SellOrder<
+        <T as Config>::KittyIndex,
+        BalanceOf<T>,
+        <T as frame_system::Config>::BlockNumber,
+    >;
+
+    /// A transfer of `kitty_id` to `to` signed off chain by the owner, which anyone can submit
+    /// through `relay_transfer` until block `deadline`. `nonce` must be the owner's next relay
+    /// nonce, so the transfer cannot be replayed.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+    pub struct RelayedTransfer<AccountId, KittyIndex, BlockNumber> {
+        pub kitty_id: KittyIndex,
+        pub to: AccountId,
+        pub nonce: u64,
+        pub deadline: BlockNumber,
+    }
+
+    pub type RelayedTransferOf<T> = RelayedTransfer<
+        <T as frame_system::Config>::AccountId,
+        <T as Config>::KittyIndex,
+        <T as frame_system::Config>::BlockNumber,
+    >;
+
+    /// A previous owner of a kitty, with the block it left them and what it sold for, if sold.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+    pub struct ProvenanceRecord<AccountId, BlockNumber, Balance, AssetId> {
+        pub previous_owner: AccountId,
+        pub block: BlockNumber,
+        pub sale_price: Option<(Balance, PaymentAsset<AssetId>)>,
+    }
+
+    pub type ProvenanceRecordOf<T> = ProvenanceRecord<
+        <T as frame_system::Config>::AccountId,
+        <T as frame_system::Config>::BlockNumber,
+        BalanceOf<T>,
+        AssetIdOf<T>,
+    >;
+
+    /// Native-currency sale volume of the current `VolumeWindow` and of the one before it.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+    pub struct SaleVolume<BlockNumber, Balance> {
+        pub window_start: BlockNumber,
+        pub current: Balance,
+        pub previous: Balance,
+    }
+
+    type SaleVolumeOf<T> = SaleVolume<<T as frame_system::Config>::BlockNumber, BalanceOf<T>>;
+
     /// Configure the pallet by specifying the parameters and types it depends on.
     #[pallet::config]
     pub trait Config: frame_system::Config {
         type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
         type Randomness: Randomness<Self::Hash, Self::BlockNumber>;
         type Currency: Currency<Self::AccountId> + ReservableCurrency<Self::AccountId>;
-        // the amount of token to deposit when mint a kitty
+        // the amount of token to deposit when mint a kitty, until `set_parameters` changes it
         #[pallet::constant]
         type MintDeposit: Get<BalanceOf<Self>>;
 
@@ -48,6 +323,66 @@ pub mod pallet {
             + Bounded
             + CheckedAdd
             + One;
+
+        // the maximum number of kitties, counting both sides, a swap can move.
+        #[pallet::constant]
+        type MaxSwapKitties: Get<u32>;
+
+        // the origin allowed to move, burn, freeze and thaw any kitty.
+        type ForceOrigin: EnsureOrigin<Self::Origin>;
+
+        // the origin allowed to pause and unpause the pallet.
+        type PauseOrigin: EnsureOrigin<Self::Origin>;
+
+        // the origin allowed to change the mint deposit and breeding fee.
+        type ParametersOrigin: EnsureOrigin<Self::Origin>;
+
+        // the maximum number of kitties `create_kitty` can ever create, bred kitties and
+        // partner collections, which have their own `max_supply`, do not count.
+        #[pallet::constant]
+        type MaxGen0Supply: Get<u32>;
+
+        // the length in blocks of the window `MaxMintsPerWindow` applies to.
+        #[pallet::constant]
+        type MintWindow: Get<Self::BlockNumber>;
+
+        // how many times an account can call `create_kitty` within one window.
+        #[pallet::constant]
+        type MaxMintsPerWindow: Get<u32>;
+
+        // the origin allowed to schedule mint phases and manage the presale allowlist.
+        type MintAdminOrigin: EnsureOrigin<Self::Origin>;
+
+        // the fungible tokens listings can be priced in, besides `Currency`.
+        type Assets: fungibles::Transfer<Self::AccountId, Balance = BalanceOf<Self>>;
+
+        // kitty wallets are sub-accounts of this id, one per kitty index.
+        #[pallet::constant]
+        type PalletId: Get<PalletId>;
+
+        // called when a kitty is minted, moved or burned.
+        type OnKittyChange: KittyLifecycleHooks<Self::AccountId, Self::KittyIndex>;
+
+        // how many previous owners are remembered per kitty, oldest are dropped first.
+        #[pallet::constant]
+        type MaxProvenance: Get<u32>;
+
+        // the length in blocks of a sale volume window, floor prices also reset every window.
+        #[pallet::constant]
+        type VolumeWindow: Get<Self::BlockNumber>;
+
+        // the maximum number of kitties a bundle can hold.
+        #[pallet::constant]
+        type MaxBundleKitties: Get<u32>;
+
+        // the signature of off-chain orders and relayed transfers, and the key it is checked
+        // against.
+        type OffchainSignature: Verify<Signer = Self::OffchainPublic> + Parameter;
+        type OffchainPublic: IdentifyAccount<AccountId = Self::AccountId>;
+
+        // the longest loan `lend` can offer, in blocks from the current one.
+        #[pallet::constant]
+        type MaxLoanPeriod: Get<Self::BlockNumber>;
     }
 
     #[pallet::pallet]
@@ -55,28 +390,439 @@ pub mod pallet {
     pub struct Pallet<T>(_);
 
     #[pallet::event]
+    #[pallet::metadata(
+        T::AccountId = "AccountId",
+        T::KittyIndex = "KittyIndex",
+        T::BlockNumber = "BlockNumber",
+        T::Hash = "Hash",
+        BalanceOf<T> = "Balance",
+        Option<BalanceOf<T>> = "Option<Balance>",
+        Option<T::AccountId> = "Option<AccountId>",
+        PaymentAsset<AssetIdOf<T>> = "PaymentAsset"
+    )]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
     pub enum Event<T: Config> {
-        KittyCreated(T::AccountId, T::KittyIndex),
-        KittyTransfered(T::AccountId, T::AccountId, T::KittyIndex),
-        PriceUpdateForSale(T::AccountId, T::KittyIndex, Option<BalanceOf<T>>),
-        Bought(T::AccountId, T::AccountId, T::KittyIndex, BalanceOf<T>),
+        /// A kitty was minted or bred. [owner, kitty_id, kitty_hash, dna]
+        KittyCreated {
+            owner: T::AccountId,
+            kitty_id: T::KittyIndex,
+            kitty_hash: T::Hash,
+            dna: [u8; 16],
+        },
+        /// A kitty changed hands. [from, to, kitty_id]
+        KittyTransfered {
+            from: T::AccountId,
+            to: T::AccountId,
+            kitty_id: T::KittyIndex,
+        },
+        /// A kitty was listed, repriced or delisted (`price` is `None`).
+        /// [owner, kitty_id, previous_price, price, asset]
+        PriceUpdateForSale {
+            owner: T::AccountId,
+            kitty_id: T::KittyIndex,
+            previous_price: Option<BalanceOf<T>>,
+            price: Option<BalanceOf<T>>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+        },
+        /// A listed kitty was bought. [buyer, seller, kitty_id, price, asset]
+        Bought {
+            buyer: T::AccountId,
+            seller: T::AccountId,
+            kitty_id: T::KittyIndex,
+            price: BalanceOf<T>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+        },
+        /// A kitty was burned. [owner, kitty_id]
+        KittyBurned {
+            owner: T::AccountId,
+            kitty_id: T::KittyIndex,
+        },
+        /// A kitty was offered for loan. [owner, borrower, kitty_id, until, fee]
+        KittyLent {
+            owner: T::AccountId,
+            borrower: T::AccountId,
+            kitty_id: T::KittyIndex,
+            until: T::BlockNumber,
+            fee: BalanceOf<T>,
+        },
+        /// The borrower accepted a loan. [borrower, kitty_id]
+        LoanAccepted {
+            borrower: T::AccountId,
+            kitty_id: T::KittyIndex,
+        },
+        /// A loan reached its end block. [kitty_id]
+        LoanEnded { kitty_id: T::KittyIndex },
+        /// The owner withdrew a loan before the borrower accepted it. [kitty_id]
+        LoanCancelled { kitty_id: T::KittyIndex },
+        /// A swap was proposed. [proposer, counterparty, swap_id]
+        SwapProposed {
+            proposer: T::AccountId,
+            counterparty: T::AccountId,
+            swap_id: SwapId,
+        },
+        /// A swap was accepted and settled. [swap_id]
+        SwapAccepted { swap_id: SwapId },
+        /// A swap was cancelled and its escrow released. [swap_id]
+        SwapCancelled { swap_id: SwapId },
+        /// Kitties were listed together as a bundle. [seller, bundle_id, price]
+        BundleListed {
+            seller: T::AccountId,
+            bundle_id: BundleId,
+            price: BalanceOf<T>,
+        },
+        /// A bundle was bought and all its kitties moved to the buyer.
+        /// [buyer, seller, bundle_id, price]
+        BundleSold {
+            buyer: T::AccountId,
+            seller: T::AccountId,
+            bundle_id: BundleId,
+            price: BalanceOf<T>,
+        },
+        /// A bundle was cancelled and its kitties unlocked. [bundle_id]
+        BundleCancelled { bundle_id: BundleId },
+        /// A signed sell order was filled, the sale itself is reported by `Bought`. [seller, nonce]
+        OrderFilled { seller: T::AccountId, nonce: u64 },
+        /// A seller cancelled the order nonce, orders signed with it can no longer be filled.
+        /// [seller, nonce]
+        OrderCancelled { seller: T::AccountId, nonce: u64 },
+        /// An account changed whether it must accept incoming transfers. [who, required]
+        RequiresAcceptanceSet { who: T::AccountId, required: bool },
+        /// A transfer is waiting for the recipient to claim it. [from, to, kitty_id]
+        TransferOffered {
+            from: T::AccountId,
+            to: T::AccountId,
+            kitty_id: T::KittyIndex,
+        },
+        /// A pending transfer was rejected or withdrawn. [who, kitty_id]
+        TransferRejected {
+            who: T::AccountId,
+            kitty_id: T::KittyIndex,
+        },
+        /// A kitty was frozen by the force origin. [kitty_id]
+        KittyFrozen { kitty_id: T::KittyIndex },
+        /// A frozen kitty was thawed. [kitty_id]
+        KittyThawed { kitty_id: T::KittyIndex },
+        /// Part of the pallet was paused. [scope]
+        Paused { scope: PauseScope },
+        /// The pallet was unpaused.
+        Unpaused,
+        /// Economic parameters were updated. [mint_deposit, breeding_fee, fee_destination]
+        ParametersSet {
+            mint_deposit: BalanceOf<T>,
+            breeding_fee: BalanceOf<T>,
+            fee_destination: Option<T::AccountId>,
+        },
+        /// Mint phases were scheduled. [presale_start, public_start]
+        MintPhasesScheduled {
+            presale_start: T::BlockNumber,
+            public_start: T::BlockNumber,
+        },
+        /// Presale terms were updated. [quota, price]
+        PresaleTermsSet {
+            quota: u32,
+            price: Option<(BalanceOf<T>, T::AccountId)>,
+        },
+        /// An account was added to or removed from the presale allowlist. [who, allowed]
+        AllowlistUpdated { who: T::AccountId, allowed: bool },
+        /// A collection was created. [owner, collection_id]
+        CollectionCreated {
+            owner: T::AccountId,
+            collection_id: CollectionId,
+        },
+        /// An empty collection was destroyed. [collection_id]
+        CollectionDestroyed { collection_id: CollectionId },
+        /// Another pallet locked a kitty. [kitty_id, reason]
+        KittyLockSet {
+            kitty_id: T::KittyIndex,
+            reason: LockReason,
+        },
+        /// A lock was lifted from a kitty. [kitty_id, reason]
+        KittyLockRemoved {
+            kitty_id: T::KittyIndex,
+            reason: LockReason,
+        },
+        /// The owner spent from a kitty's wallet. [owner, kitty_id, dest, amount]
+        KittyWalletTransfer {
+            owner: T::AccountId,
+            kitty_id: T::KittyIndex,
+            dest: T::AccountId,
+            amount: BalanceOf<T>,
+        },
+    }
+
+    #[pallet::type_value]
+    pub fn FirstKittyId<T: Config>() -> T::KittyIndex {
+        One::one()
     }
 
+    // the index the next minted kitty gets.
     #[pallet::storage]
-    #[pallet::getter(fn kitties_count)]
-    pub type KittiesCount<T: Config> = StorageValue<_, T::KittyIndex>;
+    #[pallet::getter(fn next_kitty_id)]
+    pub type NextKittyId<T: Config> = StorageValue<_, T::KittyIndex, ValueQuery, FirstKittyId<T>>;
+
+    // kitties ever minted, including burned ones.
+    #[pallet::storage]
+    #[pallet::getter(fn total_minted)]
+    pub type TotalMinted<T: Config> = StorageValue<_, T::KittyIndex, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn total_burned)]
+    pub type TotalBurned<T: Config> = StorageValue<_, T::KittyIndex, ValueQuery>;
+
+    // kitties currently alive, `TotalMinted - TotalBurned`.
+    #[pallet::storage]
+    #[pallet::getter(fn live_supply)]
+    pub type LiveSupply<T: Config> = StorageValue<_, T::KittyIndex, ValueQuery>;
 
     #[pallet::storage]
     #[pallet::getter(fn kitties)]
-    pub type Kitties<T: Config> =
-        StorageMap<_, Blake2_128Concat, T::KittyIndex, Option<Kitty<T>>, ValueQuery>;
+    pub type Kitties<T: Config> = StorageDoubleMap<
+        _,
+        Twox64Concat,
+        CollectionId,
+        Blake2_128Concat,
+        T::KittyIndex,
+        Kitty<T>,
+    >;
+
+    // content id of a kitty, independent of transaction ordering. kitties minted
+    // before content ids existed only have an index.
+    #[pallet::storage]
+    #[pallet::getter(fn kitty_hash)]
+    pub type KittyHashes<T: Config> = StorageMap<_, Blake2_128Concat, T::KittyIndex, T::Hash>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn kitty_id_by_hash)]
+    pub type KittyIdByHash<T: Config> = StorageMap<_, Identity, T::Hash, T::KittyIndex>;
+
+    // kitties minted as non-transferable badges, fixed at mint time.
+    #[pallet::storage]
+    #[pallet::getter(fn soulbound)]
+    pub type Soulbound<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, bool, ValueQuery>;
+
+    // locks other pallets hold on a kitty, kept sorted.
+    #[pallet::storage]
+    #[pallet::getter(fn kitty_locks)]
+    pub type KittyLocks<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, Vec<LockReason>, ValueQuery>;
+
+    // previous owners of a kitty, oldest first, at most `MaxProvenance` of them.
+    #[pallet::storage]
+    #[pallet::getter(fn provenance)]
+    pub type Provenance<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, Vec<ProvenanceRecordOf<T>>, ValueQuery>;
+
+    // what a listed kitty is paid in, the price itself is `Kitty::price`.
+    #[pallet::storage]
+    #[pallet::getter(fn listing_asset)]
+    pub type ListingAssets<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, PaymentAsset<AssetIdOf<T>>, ValueQuery>;
+
+    // native-currency listings keyed by big-endian price, so that iterating the map walks them
+    // from the cheapest up. listings in other assets are not indexed.
+    #[pallet::storage]
+    pub type ListingIndex<T: Config> = StorageDoubleMap<
+        _,
+        Identity,
+        [u8; 16],
+        Blake2_128Concat,
+        T::KittyIndex,
+        BalanceOf<T>,
+    >;
+
+    // the collection a kitty belongs to, kitty indices are unique across collections.
+    #[pallet::storage]
+    #[pallet::getter(fn collection_of)]
+    pub type KittyCollection<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, CollectionId, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn collections)]
+    pub type Collections<T: Config> =
+        StorageMap<_, Twox64Concat, CollectionId, CollectionConfigOf<T>>;
+
+    #[pallet::type_value]
+    pub fn FirstCollectionId() -> CollectionId {
+        1
+    }
+
+    #[pallet::storage]
+    #[pallet::getter(fn next_collection_id)]
+    pub type NextCollectionId<T: Config> =
+        StorageValue<_, CollectionId, ValueQuery, FirstCollectionId>;
+
+    // the number of live kitties in a collection.
+    #[pallet::storage]
+    #[pallet::getter(fn collection_supply)]
+    pub type CollectionSupply<T: Config> =
+        StorageMap<_, Twox64Concat, CollectionId, u32, ValueQuery>;
+
+    #[pallet::storage]
+    pub type StorageVersion<T: Config> = StorageValue<_, Releases, ValueQuery>;
 
     #[pallet::storage]
     #[pallet::getter(fn owner)]
     pub type Owner<T: Config> =
         StorageMap<_, Blake2_128Concat, T::KittyIndex, Option<T::AccountId>, ValueQuery>;
 
+    // the (sire, dam) a bred kitty comes from, kitties created from nothing have no entry.
+    #[pallet::storage]
+    #[pallet::getter(fn parents)]
+    pub type Parents<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, (T::KittyIndex, T::KittyIndex)>;
+
+    // who reserved the mint deposit of a kitty and how much, released on first transfer or burn.
+    #[pallet::storage]
+    pub type KittyDeposits<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, (T::AccountId, BalanceOf<T>)>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn loans)]
+    pub type Loans<T: Config> = StorageMap<_, Blake2_128Concat, T::KittyIndex, LoanOf<T>>;
+
+    // kitties whose loan ends at a block, cleared in `on_initialize`.
+    #[pallet::storage]
+    pub type LoanExpiries<T: Config> =
+        StorageMap<_, Twox64Concat, T::BlockNumber, Vec<T::KittyIndex>, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn next_swap_id)]
+    pub type NextSwapId<T: Config> = StorageValue<_, SwapId, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn swaps)]
+    pub type Swaps<T: Config> = StorageMap<_, Twox64Concat, SwapId, SwapOf<T>>;
+
+    // kitties held in escrow by an open swap.
+    #[pallet::storage]
+    #[pallet::getter(fn swap_escrow)]
+    pub type SwapEscrow<T: Config> = StorageMap<_, Blake2_128Concat, T::KittyIndex, SwapId>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn next_bundle_id)]
+    pub type NextBundleId<T: Config> = StorageValue<_, BundleId, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn bundles)]
+    pub type Bundles<T: Config> = StorageMap<_, Twox64Concat, BundleId, BundleOf<T>>;
+
+    // order nonces each seller has filled or cancelled.
+    #[pallet::storage]
+    #[pallet::getter(fn order_nonce_used)]
+    pub type UsedOrderNonces<T: Config> =
+        StorageDoubleMap<_, Blake2_128Concat, T::AccountId, Twox64Concat, u64, bool, ValueQuery>;
+
+    // the nonce the next relayed transfer signed by an account must carry.
+    #[pallet::storage]
+    #[pallet::getter(fn relay_nonce)]
+    pub type RelayNonces<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, u64, ValueQuery>;
+
+    // kitties locked in an open bundle.
+    #[pallet::storage]
+    #[pallet::getter(fn bundle_of)]
+    pub type BundledKitties<T: Config> = StorageMap<_, Blake2_128Concat, T::KittyIndex, BundleId>;
+
+    // accounts that only take kitties through `offer_transfer` and `claim_transfer`.
+    #[pallet::storage]
+    #[pallet::getter(fn requires_acceptance)]
+    pub type RequiresAcceptance<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, bool, ValueQuery>;
+
+    // the recipient of a kitty offered through `offer_transfer`, waiting for a claim or rejection.
+    #[pallet::storage]
+    #[pallet::getter(fn pending_transfers)]
+    pub type PendingTransfers<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, T::AccountId>;
+
+    // kitties frozen by `ForceOrigin`, their owners cannot do anything with them.
+    #[pallet::storage]
+    #[pallet::getter(fn frozen)]
+    pub type Frozen<T: Config> = StorageMap<_, Blake2_128Concat, T::KittyIndex, bool, ValueQuery>;
+
+    // the number of kitties ever created from nothing in collection 0, e.g. by `create_kitty`.
+    #[pallet::storage]
+    #[pallet::getter(fn gen0_supply)]
+    pub type Gen0Supply<T: Config> = StorageValue<_, u32, ValueQuery>;
+
+    // the start of the last window an account minted in and how many kitties it minted in it.
+    #[pallet::storage]
+    #[pallet::getter(fn mint_window_of)]
+    pub type MintWindows<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, (T::BlockNumber, u32)>;
+
+    // the blocks the presale and the public phase start at, minting is public if there is none.
+    #[pallet::storage]
+    #[pallet::getter(fn mint_schedule)]
+    pub type MintSchedule<T: Config> = StorageValue<_, (T::BlockNumber, T::BlockNumber)>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn presale_terms)]
+    pub type Presale<T: Config> = StorageValue<_, PresaleTermsOf<T>>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn allowlisted)]
+    pub type Allowlist<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, bool, ValueQuery>;
+
+    // how many kitties an account has created during the presale.
+    #[pallet::storage]
+    #[pallet::getter(fn presale_minted)]
+    pub type PresaleMinted<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+    #[pallet::type_value]
+    pub fn DefaultParameters<T: Config>() -> EconomicParametersOf<T> {
+        EconomicParameters {
+            mint_deposit: T::MintDeposit::get(),
+            breeding_fee: Zero::zero(),
+            fee_destination: None,
+        }
+    }
+
+    #[pallet::storage]
+    #[pallet::getter(fn parameters)]
+    pub type Parameters<T: Config> =
+        StorageValue<_, EconomicParametersOf<T>, ValueQuery, DefaultParameters<T>>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn paused)]
+    pub type Paused<T: Config> = StorageValue<_, PauseScope, ValueQuery>;
+
+    /// Number of live kitties carrying a trait, keyed by (slot, variant).
+    #[pallet::storage]
+    #[pallet::getter(fn trait_count)]
+    pub type TraitCounts<T: Config> =
+        StorageDoubleMap<_, Twox64Concat, u8, Twox64Concat, u8, u32, ValueQuery>;
+
+    // how many breedings separate a kitty from gen0, gen0 kitties have no entry.
+    #[pallet::storage]
+    #[pallet::getter(fn generation)]
+    pub type Generation<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, u32, ValueQuery>;
+
+    // what a kitty last sold for through `buy_kitty`, sales in other assets are not recorded.
+    #[pallet::storage]
+    #[pallet::getter(fn last_sale_price)]
+    pub type LastSalePrice<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::KittyIndex, BalanceOf<T>>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn sale_volume)]
+    pub type Volume<T: Config> = StorageValue<_, SaleVolumeOf<T>, ValueQuery>;
+
+    // the lowest sale price of a generation, and the window it was seen in.
+    #[pallet::storage]
+    #[pallet::getter(fn generation_floor)]
+    pub type GenerationFloor<T: Config> =
+        StorageMap<_, Twox64Concat, u32, (T::BlockNumber, BalanceOf<T>)>;
+
+    // the lowest sale price of kitties sharing a dominant trait, and the window it was seen in.
+    #[pallet::storage]
+    #[pallet::getter(fn trait_floor)]
+    pub type TraitFloor<T: Config> =
+        StorageMap<_, Twox64Concat, u8, (T::BlockNumber, BalanceOf<T>)>;
+
     // Errors.
     #[pallet::error]
     pub enum Error<T> {
@@ -89,18 +835,141 @@ pub mod pallet {
         BuyerIsKittyOwner,
         KittyNotForSale,
         NotEnoughBalance,
+        IncompatibleParents,
+        Inbreeding,
+        KittyOnLoan,
+        InvalidLoanPeriod,
+        LoanNotFound,
+        NotBorrower,
+        BorrowerIsKittyOwner,
+        KittyInEscrow,
+        EmptySwap,
+        SwapWithSelf,
+        TooManyKitties,
+        DuplicateKitty,
+        SwapIdOverflow,
+        SwapNotFound,
+        NotSwapCounterparty,
+        NotSwapProposer,
+        RecipientRequiresAcceptance,
+        KittyTransferPending,
+        TransferNotFound,
+        NotRecipient,
+        KittyFrozen,
+        KittyNotFrozen,
+        PalletPaused,
+        Gen0SupplyExhausted,
+        MintRateLimited,
+        MintClosed,
+        NotAllowlisted,
+        PresaleQuotaExceeded,
+        InvalidMintSchedule,
+        CollectionNotFound,
+        CollectionIdOverflow,
+        NotCollectionOwner,
+        CollectionNotEmpty,
+        CollectionSupplyExhausted,
+        CollectionMismatch,
+        BreedingDisabled,
+        KittySoulbound,
+        UnknownKittyHash,
+        KittyLocked,
+        NoListingWithinPrice,
+        KittyInBundle,
+        EmptyBundle,
+        BundleIdOverflow,
+        BundleNotFound,
+        NotBundleSeller,
+        BundlePriceTooHigh,
+        OrderExpired,
+        OrderNonceUsed,
+        InvalidSignature,
+        RelayDeadlinePassed,
+        InvalidRelayNonce,
+        LoanTooLong,
+        PaymentAssetMismatch,
+        PriceTooHigh,
+    }
+
+    #[pallet::genesis_config]
+    #[derive(Default)]
+    pub struct GenesisConfig {}
+
+    #[pallet::genesis_build]
+    impl<T: Config> GenesisBuild<T> for GenesisConfig {
+        fn build(&self) {
+            // a new chain starts on the latest layout, `on_runtime_upgrade` has nothing to migrate.
+            // keep this in step with the last `Releases` variant.
+            StorageVersion::<T>::put(Releases::V5);
+        }
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_initialize(now: T::BlockNumber) -> Weight {
+            // loans end automatically at their `until` block.
+            let expiring = LoanExpiries::<T>::take(now);
+            for kitty_id in expiring.iter() {
+                if Loans::<T>::get(kitty_id).map_or(false, |loan| loan.until == now) {
+                    Loans::<T>::remove(kitty_id);
+                    Self::deposit_event_indexed(
+                        &[*kitty_id],
+                        &[],
+                        Event::LoanEnded {
+                            kitty_id: *kitty_id,
+                        },
+                    );
+                }
+            }
+            let n = expiring.len() as Weight;
+            T::DbWeight::get().reads_writes(1 + n, 1 + n)
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            let mut weight = T::DbWeight::get().reads(1);
+            if StorageVersion::<T>::get() == Releases::V1 {
+                weight = weight.saturating_add(Self::migrate_to_collections());
+                weight = weight.saturating_add(Self::backfill_deposits_and_traits());
+            }
+            if StorageVersion::<T>::get() == Releases::V2 {
+                weight = weight.saturating_add(Self::migrate_supply_counters());
+            }
+            if StorageVersion::<T>::get() == Releases::V3 {
+                weight = weight.saturating_add(Self::migrate_generations());
+            }
+            if StorageVersion::<T>::get() == Releases::V4 {
+                weight = weight.saturating_add(Self::migrate_listing_index());
+            }
+            weight
+        }
     }
 
     #[pallet::call]
     impl<T: Config> Pallet<T> {
         #[pallet::weight(0)]
+        #[transactional]
         pub fn create_kitty(origin: OriginFor<T>) -> DispatchResult {
             let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Minting)?;
+
+            let (window_start, minted) = Self::ensure_can_mint_gen0(&who, 0)?;
+
             let dna = Self::random_value(&who);
 
-            let kitty_id = Self::mint(who.clone(), dna)?;
+            let deposit = Self::parameters().mint_deposit;
+            let kitty_id = Self::mint(who.clone(), 0, dna, deposit, true)?;
+            Self::note_gen0_mint(&who, 0, window_start, minted);
 
-            Self::deposit_event(Event::KittyCreated(who, kitty_id));
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::KittyCreated {
+                    owner: who.clone(),
+                    kitty_id,
+                    kitty_hash: Self::kitty_hash(kitty_id).unwrap_or_default(),
+                    dna,
+                },
+            );
             Ok(())
         }
 
@@ -108,92 +977,1129 @@ pub mod pallet {
         pub fn transfer(
             origin: OriginFor<T>,
             new_owner: T::AccountId,
-            kitty_id: T::KittyIndex,
+            kitty: KittyRefOf<T>,
         ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
             let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
 
             ensure!(
                 Some(who.clone()) == Owner::<T>::get(kitty_id),
                 Error::<T>::NotOwner
             );
-
-            Self::transfer_kitty_to(kitty_id, &new_owner)?;
-            Self::deposit_event(Event::KittyTransfered(who, new_owner, kitty_id));
-            Ok(())
+            Self::do_transfer(&who, &new_owner, kitty_id)
         }
 
+        /// Transfer a kitty on behalf of its owner, who signed `payload` off chain. The caller
+        /// pays the fee, so owners without native tokens can still move their kitties.
         #[pallet::weight(0)]
-        pub fn breed(
+        #[transactional]
+        pub fn relay_transfer(
             origin: OriginFor<T>,
-            kitty_id_1: T::KittyIndex,
-            kitty_id_2: T::KittyIndex,
+            payload: RelayedTransferOf<T>,
+            signature: T::OffchainSignature,
         ) -> DispatchResult {
-            let who = ensure_signed(origin)?;
-            ensure!(kitty_id_1 != kitty_id_2, Error::<T>::SameParentIndex);
+            ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
 
-            let kitty1 = Self::kitties(kitty_id_1).ok_or(Error::<T>::InvalidKittyIndex)?;
-            let kitty2 = Self::kitties(kitty_id_2).ok_or(Error::<T>::InvalidKittyIndex)?;
+            ensure!(
+                <frame_system::Pallet<T>>::block_number() <= payload.deadline,
+                Error::<T>::RelayDeadlinePassed
+            );
+            let kitty_id = payload.kitty_id;
+            let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            let nonce = Self::relay_nonce(&owner);
+            ensure!(payload.nonce == nonce, Error::<T>::InvalidRelayNonce);
+            let message = (RELAYED_TRANSFER_CONTEXT, Self::genesis_hash(), &payload).encode();
+            ensure!(
+                signature.verify(&message[..], &owner),
+                Error::<T>::InvalidSignature
+            );
 
-            let dna_1 = kitty1.dna;
-            let dna_2 = kitty2.dna;
+            RelayNonces::<T>::insert(&owner, nonce.saturating_add(1));
+            Self::do_transfer(&owner, &payload.to, kitty_id)
+        }
 
-            let selector = Self::random_value(&who);
-            let mut new_dna = [0u8; 16];
+        // 设置是否只接受需要确认的转让
+        #[pallet::weight(0)]
+        pub fn set_requires_acceptance(origin: OriginFor<T>, required: bool) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
 
-            for i in 0..dna_1.len() {
-                new_dna[i] = (selector[i] & dna_1[i]) | (!selector[i] & dna_2[i]);
+            if required {
+                RequiresAcceptance::<T>::insert(&who, true);
+            } else {
+                RequiresAcceptance::<T>::remove(&who);
             }
 
-            let kitty_id = Self::mint(who.clone(), new_dna)?;
-
-            Self::deposit_event(Event::KittyCreated(who, kitty_id));
+            Self::deposit_event_indexed(
+                &[],
+                &[&who],
+                Event::RequiresAcceptanceSet {
+                    who: who.clone(),
+                    required,
+                },
+            );
             Ok(())
         }
 
         #[pallet::weight(0)]
-        pub fn sell_kitty(
+        pub fn offer_transfer(
             origin: OriginFor<T>,
-            kitty_id: T::KittyIndex,
-            price: Option<BalanceOf<T>>,
+            new_owner: T::AccountId,
+            kitty: KittyRefOf<T>,
         ) -> DispatchResult {
-            //此处注意, 必须先判断kitty_id是否存在, 否则无论kitty存在与否, 都会先报错NotOwner
-            let mut kitty = Self::kitties(&kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+            let kitty_id = Self::resolve_kitty(kitty)?;
             let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
             ensure!(
                 Some(who.clone()) == Owner::<T>::get(kitty_id),
                 Error::<T>::NotOwner
             );
+            Self::ensure_unlocked(kitty_id)?;
+            Self::ensure_transferable(kitty_id)?;
 
-            kitty.price = price.clone();
-            Kitties::<T>::insert(kitty_id, Some(kitty));
-            Self::deposit_event(Event::PriceUpdateForSale(who, kitty_id, price));
+            PendingTransfers::<T>::insert(kitty_id, new_owner.clone());
+            Self::delist(kitty_id);
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who, &new_owner],
+                Event::TransferOffered {
+                    from: who.clone(),
+                    to: new_owner.clone(),
+                    kitty_id,
+                },
+            );
             Ok(())
         }
 
         #[pallet::weight(0)]
-        pub fn buy_kitty(origin: OriginFor<T>, kitty_id: T::KittyIndex) -> DispatchResult {
-            let buyer = ensure_signed(origin)?;
+        #[transactional]
+        pub fn claim_transfer(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
 
-            ensure!(
-                Some(buyer.clone()) != Owner::<T>::get(kitty_id),
-                Error::<T>::BuyerIsKittyOwner
+            let recipient = Self::pending_transfers(kitty_id).ok_or(Error::<T>::TransferNotFound)?;
+            ensure!(recipient == who, Error::<T>::NotRecipient);
+            let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+
+            PendingTransfers::<T>::remove(kitty_id);
+            Self::transfer_kitty_to(kitty_id, &who, None)?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&owner, &who],
+                Event::KittyTransfered {
+                    from: owner.clone(),
+                    to: who.clone(),
+                    kitty_id,
+                },
             );
+            Ok(())
+        }
 
-            let kitty = Self::kitties(&kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
-            let sale_price = kitty.price.ok_or(Error::<T>::KittyNotForSale)?;
+        // 接收方拒绝, 或者kitty主人撤回一个待确认的转让
+        #[pallet::weight(0)]
+        pub fn reject_transfer(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            let recipient = Self::pending_transfers(kitty_id).ok_or(Error::<T>::TransferNotFound)?;
+            ensure!(
+                recipient == who || Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotRecipient
+            );
+            // 冻结的kitty主人不能撤回转让, 接收方仍然可以拒绝
+            if recipient != who {
+                Self::ensure_not_frozen(kitty_id)?;
+            }
+
+            PendingTransfers::<T>::remove(kitty_id);
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::TransferRejected {
+                    who: who.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn breed(
+            origin: OriginFor<T>,
+            kitty_1: KittyRefOf<T>,
+            kitty_2: KittyRefOf<T>,
+        ) -> DispatchResult {
+            let kitty_id_1 = Self::resolve_kitty(kitty_1)?;
+            let kitty_id_2 = Self::resolve_kitty(kitty_2)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Breeding)?;
+            ensure!(kitty_id_1 != kitty_id_2, Error::<T>::SameParentIndex);
+
+            let kitty1 = Self::kitty(kitty_id_1).ok_or(Error::<T>::InvalidKittyIndex)?;
+            let kitty2 = Self::kitty(kitty_id_2).ok_or(Error::<T>::InvalidKittyIndex)?;
+
+            ensure!(
+                Self::can_use(&who, kitty_id_1) && Self::can_use(&who, kitty_id_2),
+                Error::<T>::NotOwner
+            );
+            Self::ensure_not_frozen(kitty_id_1)?;
+            Self::ensure_not_frozen(kitty_id_2)?;
+            ensure!(
+                kitty1.gender() != kitty2.gender(),
+                Error::<T>::IncompatibleParents
+            );
+            Self::ensure_not_related(kitty_id_1, kitty_id_2)?;
+
+            // the child joins the collection of its parents.
+            let collection_id = Self::collection_of(kitty_id_1);
+            ensure!(
+                collection_id == Self::collection_of(kitty_id_2),
+                Error::<T>::CollectionMismatch
+            );
+            ensure!(
+                Self::collections(collection_id).map_or(true, |config| config.breeding_enabled),
+                Error::<T>::BreedingDisabled
+            );
+
+            let dna_1 = kitty1.dna;
+            let dna_2 = kitty2.dna;
+
+            let selector = Self::random_value(&who);
+            let mut new_dna = [0u8; 16];
+
+            for i in 0..dna_1.len() {
+                new_dna[i] = (selector[i] & dna_1[i]) | (!selector[i] & dna_2[i]);
+            }
+
+            let deposit = Self::parameters().mint_deposit;
+            let kitty_id = Self::mint(who.clone(), collection_id, new_dna, deposit, true)?;
+            Self::charge_breeding_fee(&who)?;
+            let parents = match kitty1.gender() {
+                Gender::Male => (kitty_id_1, kitty_id_2),
+                Gender::Female => (kitty_id_2, kitty_id_1),
+            };
+            Parents::<T>::insert(kitty_id, parents);
+            let generation = Self::generation(kitty_id_1).max(Self::generation(kitty_id_2));
+            Generation::<T>::insert(kitty_id, generation.saturating_add(1));
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::KittyCreated {
+                    owner: who.clone(),
+                    kitty_id,
+                    kitty_hash: Self::kitty_hash(kitty_id).unwrap_or_default(),
+                    dna: new_dna,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn sell_kitty(
+            origin: OriginFor<T>,
+            kitty: KittyRefOf<T>,
+            price: Option<BalanceOf<T>>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            Self::list_kitty(origin, kitty_id, price, PaymentAsset::Native)
+        }
+
+        // 以pallet_assets中的代币标价出售kitty
+        #[pallet::weight(0)]
+        pub fn sell_kitty_for_asset(
+            origin: OriginFor<T>,
+            kitty: KittyRefOf<T>,
+            price: Option<BalanceOf<T>>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            Self::list_kitty(origin, kitty_id, price, asset)
+        }
+
+        // 买家给出预期的支付资产和最高价格, 卖家在交易打包前改价或者换资产时购买失败
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn buy_kitty(
+            origin: OriginFor<T>,
+            kitty: KittyRefOf<T>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+            max_price: BalanceOf<T>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let buyer = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+            Self::do_buy(buyer, kitty_id, asset, max_price)
+        }
 
+        /// Buy the cheapest kitty listed in the native currency, as long as it costs no more than
+        /// `max_price`. The caller's own listings are skipped, but only the `MAX_CHEAPEST_SCAN`
+        /// cheapest listings are looked at.
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn buy_cheapest(origin: OriginFor<T>, max_price: BalanceOf<T>) -> DispatchResult {
+            let buyer = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            let kitty_id = ListingIndex::<T>::iter()
+                .take(MAX_CHEAPEST_SCAN)
+                .take_while(|(_, _, price)| *price <= max_price)
+                .map(|(_, kitty_id, _)| kitty_id)
+                .find(|kitty_id| Owner::<T>::get(kitty_id).as_ref() != Some(&buyer))
+                .ok_or(Error::<T>::NoListingWithinPrice)?;
+            Self::do_buy(buyer, kitty_id, PaymentAsset::Native, max_price)
+        }
+
+        /// Buy a kitty through an order its owner signed off chain, without a `sell_kitty`.
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn fill_order(
+            origin: OriginFor<T>,
+            seller: T::AccountId,
+            order: SellOrderOf<T>,
+            signature: T::OffchainSignature,
+        ) -> DispatchResult {
+            let buyer = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            ensure!(
+                <frame_system::Pallet<T>>::block_number() <= order.expiry,
+                Error::<T>::OrderExpired
+            );
             ensure!(
-                T::Currency::free_balance(&buyer) >= sale_price,
+                !Self::order_nonce_used(&seller, order.nonce),
+                Error::<T>::OrderNonceUsed
+            );
+            let payload = (SELL_ORDER_CONTEXT, Self::genesis_hash(), &order).encode();
+            ensure!(
+                signature.verify(&payload[..], &seller),
+                Error::<T>::InvalidSignature
+            );
+
+            let kitty_id = order.kitty_id;
+            let kitty = Self::kitty(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+            ensure!(
+                Some(seller.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+            ensure!(buyer != seller, Error::<T>::BuyerIsKittyOwner);
+
+            UsedOrderNonces::<T>::insert(&seller, order.nonce, true);
+            Self::settle_sale(
+                &buyer,
+                &seller,
+                kitty_id,
+                &kitty,
+                order.price,
+                PaymentAsset::Native,
+            )?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&seller],
+                Event::OrderFilled {
+                    seller: seller.clone(),
+                    nonce: order.nonce,
+                },
+            );
+            Ok(())
+        }
+
+        /// Invalidate every order signed with `nonce`.
+        #[pallet::weight(0)]
+        pub fn cancel_order_nonce(origin: OriginFor<T>, nonce: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            ensure!(
+                !Self::order_nonce_used(&who, nonce),
+                Error::<T>::OrderNonceUsed
+            );
+            UsedOrderNonces::<T>::insert(&who, nonce, true);
+            Self::deposit_event_indexed(
+                &[],
+                &[&who],
+                Event::OrderCancelled {
+                    seller: who.clone(),
+                    nonce,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn burn(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            ensure!(
+                Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+
+            Self::ensure_unlocked(kitty_id)?;
+
+            Self::burn_kitty(kitty_id)?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::KittyBurned {
+                    owner: who.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        // 出租kitty, 租借期间kitty不能转让或出售, 到期自动归还使用权
+        #[pallet::weight(0)]
+        pub fn lend(
+            origin: OriginFor<T>,
+            kitty: KittyRefOf<T>,
+            borrower: T::AccountId,
+            until_block: T::BlockNumber,
+            fee: BalanceOf<T>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            ensure!(
+                Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+            ensure!(who != borrower, Error::<T>::BorrowerIsKittyOwner);
+            Self::ensure_unlocked(kitty_id)?;
+            let now = <frame_system::Pallet<T>>::block_number();
+            ensure!(until_block > now, Error::<T>::InvalidLoanPeriod);
+            ensure!(
+                until_block - now <= T::MaxLoanPeriod::get(),
+                Error::<T>::LoanTooLong
+            );
+
+            Loans::<T>::insert(
+                kitty_id,
+                Loan {
+                    borrower: borrower.clone(),
+                    until: until_block,
+                    fee,
+                    accepted: false,
+                },
+            );
+            LoanExpiries::<T>::append(until_block, kitty_id);
+            Self::delist(kitty_id);
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who, &borrower],
+                Event::KittyLent {
+                    owner: who.clone(),
+                    borrower: borrower.clone(),
+                    kitty_id,
+                    until: until_block,
+                    fee,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn accept_loan(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            let mut loan = Self::loans(kitty_id).ok_or(Error::<T>::LoanNotFound)?;
+            ensure!(loan.borrower == who, Error::<T>::NotBorrower);
+            ensure!(!loan.accepted, Error::<T>::KittyOnLoan);
+            Self::ensure_not_frozen(kitty_id)?;
+
+            let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            T::Currency::transfer(&who, &owner, loan.fee, ExistenceRequirement::KeepAlive)?;
+
+            loan.accepted = true;
+            Loans::<T>::insert(kitty_id, loan);
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::LoanAccepted {
+                    borrower: who.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        // 借用人接受之前, 主人可以撤回出租
+        #[pallet::weight(0)]
+        pub fn cancel_loan(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            ensure!(
+                Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+            Self::ensure_not_frozen(kitty_id)?;
+            let loan = Self::loans(kitty_id).ok_or(Error::<T>::LoanNotFound)?;
+            ensure!(!loan.accepted, Error::<T>::KittyOnLoan);
+
+            Loans::<T>::remove(kitty_id);
+            LoanExpiries::<T>::mutate(loan.until, |expiring| expiring.retain(|id| *id != kitty_id));
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who, &loan.borrower],
+                Event::LoanCancelled { kitty_id },
+            );
+            Ok(())
+        }
+
+        // 发起kitty交换, 自己的kitty和附加的金额会被托管, 直到对方接受或自己取消
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn propose_swap(
+            origin: OriginFor<T>,
+            my_kitties: Vec<KittyRefOf<T>>,
+            their_kitties: Vec<KittyRefOf<T>>,
+            their_account: T::AccountId,
+            sweetener: Option<BalanceOf<T>>,
+        ) -> DispatchResult {
+            let my_kitties = Self::resolve_kitties(my_kitties)?;
+            let their_kitties = Self::resolve_kitties(their_kitties)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            ensure!(who != their_account, Error::<T>::SwapWithSelf);
+            ensure!(
+                !my_kitties.is_empty() || !their_kitties.is_empty(),
+                Error::<T>::EmptySwap
+            );
+            ensure!(
+                my_kitties.len() + their_kitties.len() <= T::MaxSwapKitties::get() as usize,
+                Error::<T>::TooManyKitties
+            );
+            Self::ensure_distinct(&my_kitties)?;
+            Self::ensure_distinct(&their_kitties)?;
+
+            for kitty_id in my_kitties.iter() {
+                ensure!(
+                    Some(who.clone()) == Owner::<T>::get(kitty_id),
+                    Error::<T>::NotOwner
+                );
+                Self::ensure_unlocked(*kitty_id)?;
+                Self::ensure_transferable(*kitty_id)?;
+            }
+            for kitty_id in their_kitties.iter() {
+                ensure!(
+                    Some(their_account.clone()) == Owner::<T>::get(kitty_id),
+                    Error::<T>::NotOwner
+                );
+                Self::ensure_not_frozen(*kitty_id)?;
+                Self::ensure_transferable(*kitty_id)?;
+            }
+
+            let swap_id = Self::next_swap_id();
+            let next_swap_id = swap_id.checked_add(1).ok_or(Error::<T>::SwapIdOverflow)?;
+
+            if let Some(amount) = sweetener {
+                T::Currency::reserve(&who, amount).map_err(|_| Error::<T>::NotEnoughBalance)?;
+            }
+            for kitty_id in my_kitties.iter() {
+                SwapEscrow::<T>::insert(kitty_id, swap_id);
+                Self::delist(*kitty_id);
+            }
+            let swap = Swap {
+                proposer: who.clone(),
+                counterparty: their_account.clone(),
+                offered: my_kitties,
+                requested: their_kitties,
+                sweetener,
+            };
+            Swaps::<T>::insert(swap_id, &swap);
+            NextSwapId::<T>::put(next_swap_id);
+
+            Self::deposit_swap_event(
+                &swap,
+                Event::SwapProposed {
+                    proposer: who,
+                    counterparty: their_account,
+                    swap_id,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn accept_swap(origin: OriginFor<T>, swap_id: SwapId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            let swap = Self::swaps(swap_id).ok_or(Error::<T>::SwapNotFound)?;
+            ensure!(swap.counterparty == who, Error::<T>::NotSwapCounterparty);
+            for kitty_id in swap.requested.iter() {
+                ensure!(
+                    Some(who.clone()) == Owner::<T>::get(kitty_id),
+                    Error::<T>::NotOwner
+                );
+            }
+
+            Self::close_swap(swap_id, &swap);
+            for kitty_id in swap.offered.iter() {
+                Self::transfer_kitty_to(*kitty_id, &who, None)?;
+            }
+            for kitty_id in swap.requested.iter() {
+                Self::transfer_kitty_to(*kitty_id, &swap.proposer, None)?;
+            }
+            if let Some(amount) = swap.sweetener {
+                T::Currency::repatriate_reserved(
+                    &swap.proposer,
+                    &who,
+                    amount,
+                    BalanceStatus::Free,
+                )?;
+            }
+
+            Self::deposit_swap_event(&swap, Event::SwapAccepted { swap_id });
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn cancel_swap(origin: OriginFor<T>, swap_id: SwapId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            let swap = Self::swaps(swap_id).ok_or(Error::<T>::SwapNotFound)?;
+            ensure!(swap.proposer == who, Error::<T>::NotSwapProposer);
+            for kitty_id in swap.offered.iter() {
+                Self::ensure_not_frozen(*kitty_id)?;
+            }
+
+            Self::close_swap(swap_id, &swap);
+            if let Some(amount) = swap.sweetener {
+                T::Currency::unreserve(&who, amount);
+            }
+
+            Self::deposit_swap_event(&swap, Event::SwapCancelled { swap_id });
+            Ok(())
+        }
+
+        // 把多只kitty作为一个整体挂单出售, 这些kitty在售出或取消前都被锁定
+        #[pallet::weight(0)]
+        pub fn list_bundle(
+            origin: OriginFor<T>,
+            kitties: Vec<KittyRefOf<T>>,
+            price: BalanceOf<T>,
+        ) -> DispatchResult {
+            let kitties = Self::resolve_kitties(kitties)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            ensure!(!kitties.is_empty(), Error::<T>::EmptyBundle);
+            ensure!(
+                kitties.len() <= T::MaxBundleKitties::get() as usize,
+                Error::<T>::TooManyKitties
+            );
+            Self::ensure_distinct(&kitties)?;
+            for kitty_id in kitties.iter() {
+                ensure!(
+                    Some(who.clone()) == Owner::<T>::get(kitty_id),
+                    Error::<T>::NotOwner
+                );
+                Self::ensure_unlocked(*kitty_id)?;
+                Self::ensure_transferable(*kitty_id)?;
+            }
+
+            let bundle_id = Self::next_bundle_id();
+            let next_bundle_id = bundle_id
+                .checked_add(1)
+                .ok_or(Error::<T>::BundleIdOverflow)?;
+
+            // a bundled kitty is only sold with its bundle.
+            for kitty_id in kitties.iter() {
+                Self::delist(*kitty_id);
+                BundledKitties::<T>::insert(kitty_id, bundle_id);
+            }
+            let bundle = Bundle {
+                seller: who.clone(),
+                kitties,
+                price,
+            };
+            Bundles::<T>::insert(bundle_id, &bundle);
+            NextBundleId::<T>::put(next_bundle_id);
+
+            Self::deposit_event_indexed(
+                &bundle.kitties,
+                &[&who],
+                Event::BundleListed {
+                    seller: who.clone(),
+                    bundle_id,
+                    price,
+                },
+            );
+            Ok(())
+        }
+
+        // 一次性买下整个bundle, 任何一只kitty转让失败则全部回滚
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn buy_bundle(
+            origin: OriginFor<T>,
+            bundle_id: BundleId,
+            max_price: BalanceOf<T>,
+        ) -> DispatchResult {
+            let buyer = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            let bundle = Self::bundles(bundle_id).ok_or(Error::<T>::BundleNotFound)?;
+            ensure!(bundle.seller != buyer, Error::<T>::BuyerIsKittyOwner);
+            ensure!(bundle.price <= max_price, Error::<T>::BundlePriceTooHigh);
+            ensure!(
+                T::Currency::free_balance(&buyer) >= bundle.price,
                 Error::<T>::NotEnoughBalance
             );
 
-            let seller = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            T::Currency::transfer(
+                &buyer,
+                &bundle.seller,
+                bundle.price,
+                ExistenceRequirement::KeepAlive,
+            )?;
+            Self::close_bundle(bundle_id, &bundle);
+            // 按数量平分价格记入每只kitty的流转记录, 除不尽的部分算在最后一只上;
+            // 这些份额不是真实成交价, 只计入成交量, 不更新最近成交价和地板价
+            let count: BalanceOf<T> = (bundle.kitties.len() as u32).into();
+            let share = bundle.price / count;
+            let last = bundle.kitties.len() - 1;
+            for (i, kitty_id) in bundle.kitties.iter().enumerate() {
+                let sale_price = if i == last {
+                    share + bundle.price % count
+                } else {
+                    share
+                };
+                Self::transfer_kitty_to(
+                    *kitty_id,
+                    &buyer,
+                    Some((sale_price, PaymentAsset::Native)),
+                )?;
+            }
+            Self::record_volume(bundle.price);
+
+            Self::deposit_event_indexed(
+                &bundle.kitties,
+                &[&buyer, &bundle.seller],
+                Event::BundleSold {
+                    buyer: buyer.clone(),
+                    seller: bundle.seller.clone(),
+                    bundle_id,
+                    price: bundle.price,
+                },
+            );
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn cancel_bundle(origin: OriginFor<T>, bundle_id: BundleId) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            let bundle = Self::bundles(bundle_id).ok_or(Error::<T>::BundleNotFound)?;
+            ensure!(bundle.seller == who, Error::<T>::NotBundleSeller);
+            for kitty_id in bundle.kitties.iter() {
+                Self::ensure_not_frozen(*kitty_id)?;
+            }
+
+            Self::close_bundle(bundle_id, &bundle);
+            Self::deposit_event_indexed(
+                &bundle.kitties,
+                &[&who],
+                Event::BundleCancelled { bundle_id },
+            );
+            Ok(())
+        }
+
+        // 创建一个新的kitty合集, 调用者成为合集的主人
+        #[pallet::weight(0)]
+        pub fn create_collection(
+            origin: OriginFor<T>,
+            mint_price: BalanceOf<T>,
+            max_supply: Option<u32>,
+            breeding_enabled: bool,
+            transferable: bool,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Minting)?;
+
+            let collection_id = Self::next_collection_id();
+            let next_collection_id = collection_id
+                .checked_add(1)
+                .ok_or(Error::<T>::CollectionIdOverflow)?;
+
+            Collections::<T>::insert(
+                collection_id,
+                CollectionConfig {
+                    owner: who.clone(),
+                    mint_price,
+                    max_supply,
+                    breeding_enabled,
+                    transferable,
+                },
+            );
+            NextCollectionId::<T>::put(next_collection_id);
+
+            Self::deposit_event_indexed(
+                &[],
+                &[&who],
+                Event::CollectionCreated {
+                    owner: who.clone(),
+                    collection_id,
+                },
+            );
+            Ok(())
+        }
+
+        // 只有合集主人可以销毁合集, 且合集中不能还有kitty
+        #[pallet::weight(0)]
+        pub fn destroy_collection(
+            origin: OriginFor<T>,
+            collection_id: CollectionId,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::All)?;
+
+            let config = Self::collections(collection_id).ok_or(Error::<T>::CollectionNotFound)?;
+            ensure!(config.owner == who, Error::<T>::NotCollectionOwner);
+            ensure!(
+                Self::collection_supply(collection_id) == 0,
+                Error::<T>::CollectionNotEmpty
+            );
+
+            Collections::<T>::remove(collection_id);
+            Self::deposit_event_indexed(&[], &[&who], Event::CollectionDestroyed { collection_id });
+            Ok(())
+        }
+
+        // 在某个合集中铸造kitty, 需要向合集主人支付铸造价格
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn create_kitty_in_collection(
+            origin: OriginFor<T>,
+            collection_id: CollectionId,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Minting)?;
+
+            let config = Self::collections(collection_id).ok_or(Error::<T>::CollectionNotFound)?;
+            // partner collections only share the rate limit of `create_kitty`, they have their own
+            // supply cap and mint price instead of the main drop's gen0 cap, phases and presale.
+            let (window_start, minted) = Self::ensure_can_mint_gen0(&who, collection_id)?;
+
+            if !config.mint_price.is_zero() {
+                T::Currency::transfer(
+                    &who,
+                    &config.owner,
+                    config.mint_price,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+            }
+            let dna = Self::random_value(&who);
+
+            let deposit = Self::parameters().mint_deposit;
+            let kitty_id = Self::mint(who.clone(), collection_id, dna, deposit, true)?;
+            Self::note_gen0_mint(&who, collection_id, window_start, minted);
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::KittyCreated {
+                    owner: who.clone(),
+                    kitty_id,
+                    kitty_hash: Self::kitty_hash(kitty_id).unwrap_or_default(),
+                    dna,
+                },
+            );
+            Ok(())
+        }
+
+        // kitty主人从kitty钱包转出资金, 钱包随kitty转让或出售;
+        // kitty被托管, 出租, 打包或者加锁时不能动用, 转出时会撤下挂单, 买家不会为已转走的资金付款
+        #[pallet::weight(0)]
+        pub fn kitty_wallet_transfer(
+            origin: OriginFor<T>,
+            kitty: KittyRefOf<T>,
+            dest: T::AccountId,
+            amount: BalanceOf<T>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+
+            ensure!(
+                Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+            Self::ensure_unlocked(kitty_id)?;
+
+            T::Currency::transfer(
+                &Self::kitty_wallet(kitty_id),
+                &dest,
+                amount,
+                ExistenceRequirement::AllowDeath,
+            )?;
+            Self::delist(kitty_id);
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who, &dest],
+                Event::KittyWalletTransfer {
+                    owner: who.clone(),
+                    kitty_id,
+                    dest: dest.clone(),
+                    amount,
+                },
+            );
+            Ok(())
+        }
+
+        // 管理员直接铸造kitty给某个账户, 不收押金, 可以铸造不可转让的徽章kitty
+        #[pallet::weight(0)]
+        #[transactional]
+        pub fn force_mint(
+            origin: OriginFor<T>,
+            owner: T::AccountId,
+            collection_id: CollectionId,
+            transferable: bool,
+        ) -> DispatchResult {
+            T::MintAdminOrigin::ensure_origin(origin)?;
+
+            // gen0 supply is counted like any other mint, only the per-account rate limit is
+            // skipped for admin mints.
+            Self::ensure_gen0_supply_left(collection_id)?;
+            if collection_id != 0 {
+                ensure!(
+                    Collections::<T>::contains_key(collection_id),
+                    Error::<T>::CollectionNotFound
+                );
+            }
+            let dna = Self::random_value(&owner);
+
+            let kitty_id = Self::mint(owner.clone(), collection_id, dna, Zero::zero(), transferable)?;
+            Self::note_gen0_supply(collection_id);
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&owner],
+                Event::KittyCreated {
+                    owner: owner.clone(),
+                    kitty_id,
+                    kitty_hash: Self::kitty_hash(kitty_id).unwrap_or_default(),
+                    dna,
+                },
+            );
+            Ok(())
+        }
+
+        // 管理员强制转让kitty, 会取消该kitty的出租, 交换, 待确认转让以及挂单;
+        // 其他模块加的锁不会被绕过, 需要锁的持有者先解锁
+        #[pallet::weight(0)]
+        pub fn force_transfer(
+            origin: OriginFor<T>,
+            new_owner: T::AccountId,
+            kitty: KittyRefOf<T>,
+        ) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            Self::ensure_no_locks(kitty_id)?;
+            Self::clear_encumbrances(kitty_id);
+            Self::move_kitty(kitty_id, &new_owner, None)?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&owner, &new_owner],
+                Event::KittyTransfered {
+                    from: owner.clone(),
+                    to: new_owner.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        // 管理员强制销毁kitty, 同样不能绕过其他模块加的锁
+        #[pallet::weight(0)]
+        pub fn force_burn(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                Owner::<T>::get(kitty_id).is_some(),
+                Error::<T>::OwnerNotExist
+            );
+            Self::ensure_no_locks(kitty_id)?;
+            Self::clear_encumbrances(kitty_id);
+            let owner = Self::burn_kitty(kitty_id)?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&owner],
+                Event::KittyBurned {
+                    owner: owner.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        // 暂停铸造, 繁殖, 交易或者整个模块, PauseScope::Nothing 表示恢复
+        #[pallet::weight(0)]
+        pub fn set_paused(origin: OriginFor<T>, scope: PauseScope) -> DispatchResult {
+            T::PauseOrigin::ensure_origin(origin)?;
+
+            Paused::<T>::put(scope);
+            if scope == PauseScope::Nothing {
+                Self::deposit_event(Event::Unpaused);
+            } else {
+                Self::deposit_event(Event::Paused { scope });
+            }
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn set_parameters(
+            origin: OriginFor<T>,
+            mint_deposit: BalanceOf<T>,
+            breeding_fee: BalanceOf<T>,
+            fee_destination: Option<T::AccountId>,
+        ) -> DispatchResult {
+            T::ParametersOrigin::ensure_origin(origin)?;
+
+            Parameters::<T>::put(EconomicParameters {
+                mint_deposit,
+                breeding_fee,
+                fee_destination: fee_destination.clone(),
+            });
+            Self::deposit_event(Event::ParametersSet {
+                mint_deposit,
+                breeding_fee,
+                fee_destination,
+            });
+            Ok(())
+        }
+
+        // 设置预售和公开铸造的开始区块, 预售开始之前不能铸造
+        #[pallet::weight(0)]
+        pub fn schedule_mint_phases(
+            origin: OriginFor<T>,
+            presale_start: T::BlockNumber,
+            public_start: T::BlockNumber,
+        ) -> DispatchResult {
+            T::MintAdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                presale_start <= public_start,
+                Error::<T>::InvalidMintSchedule
+            );
+
+            MintSchedule::<T>::put((presale_start, public_start));
+            Self::deposit_event(Event::MintPhasesScheduled {
+                presale_start,
+                public_start,
+            });
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn set_presale_terms(
+            origin: OriginFor<T>,
+            quota: u32,
+            price: Option<(BalanceOf<T>, T::AccountId)>,
+        ) -> DispatchResult {
+            T::MintAdminOrigin::ensure_origin(origin)?;
+
+            Presale::<T>::put(PresaleTerms {
+                quota,
+                price: price.clone(),
+            });
+            Self::deposit_event(Event::PresaleTermsSet { quota, price });
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn add_to_allowlist(
+            origin: OriginFor<T>,
+            accounts: Vec<T::AccountId>,
+        ) -> DispatchResult {
+            T::MintAdminOrigin::ensure_origin(origin)?;
+
+            for account in accounts {
+                Allowlist::<T>::insert(&account, true);
+                Self::deposit_event_indexed(
+                    &[],
+                    &[&account],
+                    Event::AllowlistUpdated {
+                        who: account.clone(),
+                        allowed: true,
+                    },
+                );
+            }
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn remove_from_allowlist(
+            origin: OriginFor<T>,
+            accounts: Vec<T::AccountId>,
+        ) -> DispatchResult {
+            T::MintAdminOrigin::ensure_origin(origin)?;
+
+            for account in accounts {
+                Allowlist::<T>::remove(&account);
+                Self::deposit_event_indexed(
+                    &[],
+                    &[&account],
+                    Event::AllowlistUpdated {
+                        who: account.clone(),
+                        allowed: false,
+                    },
+                );
+            }
+            Ok(())
+        }
+
+        #[pallet::weight(0)]
+        pub fn freeze_kitty(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            T::ForceOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                Self::kitty(kitty_id).is_some(),
+                Error::<T>::InvalidKittyIndex
+            );
+            Self::ensure_not_frozen(kitty_id)?;
+
+            Frozen::<T>::insert(kitty_id, true);
+            Self::delist(kitty_id);
+            Self::deposit_event_indexed(&[kitty_id], &[], Event::KittyFrozen { kitty_id });
+            Ok(())
+        }
 
-            T::Currency::transfer(&buyer, &seller, sale_price, ExistenceRequirement::KeepAlive)?;
+        #[pallet::weight(0)]
+        pub fn thaw_kitty(origin: OriginFor<T>, kitty: KittyRefOf<T>) -> DispatchResult {
+            let kitty_id = Self::resolve_kitty(kitty)?;
+            T::ForceOrigin::ensure_origin(origin)?;
 
-            Self::transfer_kitty_to(kitty_id, &buyer)?;
+            ensure!(Self::frozen(kitty_id), Error::<T>::KittyNotFrozen);
 
-            Self::deposit_event(Event::Bought(buyer, seller, kitty_id, sale_price));
+            Frozen::<T>::remove(kitty_id);
+            Self::deposit_event_indexed(&[kitty_id], &[], Event::KittyThawed { kitty_id });
             Ok(())
         }
     }
@@ -208,32 +2114,989 @@ pub mod pallet {
             payload.using_encoded(blake2_128)
         }
         // mint 方法用于产生kitty
-        fn mint(owner: T::AccountId, dna: [u8; 16]) -> Result<T::KittyIndex, Error<T>> {
-            let new_count = match Self::kitties_count() {
-                Some(cnt) => cnt
-                    .checked_add(&One::one())
-                    .ok_or(<Error<T>>::KittiesCountOverflow)?,
-                None => One::one(),
-            };
+        fn mint(
+            owner: T::AccountId,
+            collection_id: CollectionId,
+            dna: [u8; 16],
+            deposit: BalanceOf<T>,
+            transferable: bool,
+        ) -> Result<T::KittyIndex, Error<T>> {
+            let kitty_id = Self::next_kitty_id();
+            let next_kitty_id = kitty_id
+                .checked_add(&One::one())
+                .ok_or(<Error<T>>::KittiesCountOverflow)?;
+            let supply = Self::collection_supply(collection_id);
+            let config = Self::collections(collection_id);
+            if let Some(max_supply) = config.as_ref().and_then(|c| c.max_supply) {
+                ensure!(supply < max_supply, Error::<T>::CollectionSupplyExhausted);
+            }
             // reserve tokens for mint any kitty.
-            T::Currency::reserve(&owner, T::MintDeposit::get())
-                .map_err(|_| Error::<T>::NotEnoughBalance)?;
-
-            let kitty_id = new_count;
+            T::Currency::reserve(&owner, deposit).map_err(|_| Error::<T>::NotEnoughBalance)?;
 
-            Kitties::<T>::insert(kitty_id, Some(Kitty { dna, price: None }));
+            Kitties::<T>::insert(collection_id, kitty_id, Kitty { dna, price: None });
+            KittyCollection::<T>::insert(kitty_id, collection_id);
+            CollectionSupply::<T>::insert(collection_id, supply + 1);
             Owner::<T>::insert(kitty_id, Some(owner.clone()));
-            KittiesCount::<T>::put(new_count);
+            let kitty_hash = Self::content_id(&dna, &owner);
+            KittyHashes::<T>::insert(kitty_id, kitty_hash);
+            KittyIdByHash::<T>::insert(kitty_hash, kitty_id);
+            if !deposit.is_zero() {
+                KittyDeposits::<T>::insert(kitty_id, (owner.clone(), deposit));
+            }
+            if !transferable || config.map_or(false, |c| !c.transferable) {
+                Soulbound::<T>::insert(kitty_id, true);
+            }
+            NextKittyId::<T>::put(next_kitty_id);
+            TotalMinted::<T>::mutate(|minted| *minted = minted.saturating_add(One::one()));
+            LiveSupply::<T>::mutate(|supply| *supply = supply.saturating_add(One::one()));
+            Self::add_traits(&dna);
+            T::OnKittyChange::on_mint(&owner, kitty_id);
             Ok(kitty_id)
         }
+        // 由dna, 创建者和铸造区块得出内容id, 与交易顺序无关, 如有冲突则加盐重算
+        fn content_id(dna: &[u8; 16], creator: &T::AccountId) -> T::Hash {
+            let block_number = <frame_system::Pallet<T>>::block_number();
+            let mut salt: u32 = 0;
+            loop {
+                let kitty_hash = T::Hashing::hash_of(&(dna, creator, block_number, salt));
+                if !KittyIdByHash::<T>::contains_key(kitty_hash) {
+                    return kitty_hash;
+                }
+                salt = salt.saturating_add(1);
+            }
+        }
+
+        /// The index a `KittyRef` points at.
+        pub fn resolve_kitty(kitty: KittyRefOf<T>) -> Result<T::KittyIndex, Error<T>> {
+            match kitty {
+                KittyRef::Index(kitty_id) => Ok(kitty_id),
+                KittyRef::Hash(kitty_hash) => {
+                    Self::kitty_id_by_hash(kitty_hash).ok_or(Error::<T>::UnknownKittyHash)
+                }
+            }
+        }
+
+        fn resolve_kitties(kitties: Vec<KittyRefOf<T>>) -> Result<Vec<T::KittyIndex>, Error<T>> {
+            kitties.into_iter().map(Self::resolve_kitty).collect()
+        }
+
+        // burn 销毁一个kitty, 释放押金并更新特征计数
+        fn burn_kitty(kitty_id: T::KittyIndex) -> Result<T::AccountId, Error<T>> {
+            let kitty = Self::kitty(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+            let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            // 销毁前把kitty钱包中的余额全部转给主人
+            let wallet = Self::kitty_wallet(kitty_id);
+            let funds = T::Currency::free_balance(&wallet);
+            if !funds.is_zero() {
+                T::Currency::transfer(&wallet, &owner, funds, ExistenceRequirement::AllowDeath)
+                    .map_err(|_| Error::<T>::NotEnoughBalance)?;
+            }
+            Self::delist(kitty_id);
+            let collection_id = KittyCollection::<T>::take(kitty_id);
+            Kitties::<T>::remove(collection_id, kitty_id);
+            CollectionSupply::<T>::mutate(collection_id, |supply| {
+                *supply = supply.saturating_sub(1)
+            });
+            Owner::<T>::remove(kitty_id);
+            Frozen::<T>::remove(kitty_id);
+            Soulbound::<T>::remove(kitty_id);
+            KittyLocks::<T>::remove(kitty_id);
+            Provenance::<T>::remove(kitty_id);
+            Generation::<T>::remove(kitty_id);
+            LastSalePrice::<T>::remove(kitty_id);
+            if let Some(kitty_hash) = KittyHashes::<T>::take(kitty_id) {
+                KittyIdByHash::<T>::remove(kitty_hash);
+            }
+            Self::release_deposit(kitty_id);
+            Self::remove_traits(&kitty.dna);
+            TotalBurned::<T>::mutate(|burned| *burned = burned.saturating_add(One::one()));
+            LiveSupply::<T>::mutate(|supply| *supply = supply.saturating_sub(One::one()));
+            T::OnKittyChange::on_burn(&owner, kitty_id);
+            Ok(owner)
+        }
         // transfer 一个kitty, 此为私有方法
-        fn transfer_kitty_to(kitty_id: T::KittyIndex, to: &T::AccountId) -> Result<(), Error<T>> {
-            let _kitty = Kitties::<T>::get(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+        fn transfer_kitty_to(
+            kitty_id: T::KittyIndex,
+            to: &T::AccountId,
+            sale_price: Option<(BalanceOf<T>, PaymentAsset<AssetIdOf<T>>)>,
+        ) -> Result<(), Error<T>> {
+            Self::ensure_unlocked(kitty_id)?;
+            Self::ensure_transferable(kitty_id)?;
+            Self::move_kitty(kitty_id, to, sale_price)
+        }
+        // 不检查任何锁, 只供transfer_kitty_to以及管理员调用
+        fn move_kitty(
+            kitty_id: T::KittyIndex,
+            to: &T::AccountId,
+            sale_price: Option<(BalanceOf<T>, PaymentAsset<AssetIdOf<T>>)>,
+        ) -> Result<(), Error<T>> {
+            let _kitty = Self::kitty(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
             let owner = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
             Owner::<T>::insert(kitty_id, Some(to.clone()));
             // unreserve the deposit after transfer or buy
-            T::Currency::unreserve(&owner, T::MintDeposit::get());
+            Self::release_deposit(kitty_id);
+            // a listing is an offer of the previous owner, it does not pass to the new one.
+            Self::delist(kitty_id);
+            Self::record_provenance(kitty_id, owner.clone(), sale_price);
+            T::OnKittyChange::on_transfer(&owner, to, kitty_id);
+            Ok(())
+        }
+
+        // 记录上一任主人, 超过MaxProvenance条时丢弃最早的记录
+        fn record_provenance(
+            kitty_id: T::KittyIndex,
+            previous_owner: T::AccountId,
+            sale_price: Option<(BalanceOf<T>, PaymentAsset<AssetIdOf<T>>)>,
+        ) {
+            let max = T::MaxProvenance::get() as usize;
+            Provenance::<T>::mutate(kitty_id, |records| {
+                records.push(ProvenanceRecord {
+                    previous_owner,
+                    block: <frame_system::Pallet<T>>::block_number(),
+                    sale_price,
+                });
+                if records.len() > max {
+                    let excess = records.len() - max;
+                    records.drain(..excess);
+                }
+            });
+        }
+
+        fn do_transfer(
+            from: &T::AccountId,
+            to: &T::AccountId,
+            kitty_id: T::KittyIndex,
+        ) -> DispatchResult {
+            ensure!(
+                !Self::requires_acceptance(to),
+                Error::<T>::RecipientRequiresAcceptance
+            );
+
+            Self::transfer_kitty_to(kitty_id, to, None)?;
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[from, to],
+                Event::KittyTransfered {
+                    from: from.clone(),
+                    to: to.clone(),
+                    kitty_id,
+                },
+            );
+            Ok(())
+        }
+
+        fn do_buy(
+            buyer: T::AccountId,
+            kitty_id: T::KittyIndex,
+            asset: PaymentAsset<AssetIdOf<T>>,
+            max_price: BalanceOf<T>,
+        ) -> DispatchResult {
+            ensure!(
+                Some(buyer.clone()) != Owner::<T>::get(kitty_id),
+                Error::<T>::BuyerIsKittyOwner
+            );
+
+            let kitty = Self::kitty(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+            let sale_price = kitty.price.ok_or(Error::<T>::KittyNotForSale)?;
+            ensure!(
+                Self::listing_asset(kitty_id) == asset,
+                Error::<T>::PaymentAssetMismatch
+            );
+            ensure!(sale_price <= max_price, Error::<T>::PriceTooHigh);
+
+            let seller = Owner::<T>::get(kitty_id).ok_or(Error::<T>::OwnerNotExist)?;
+            Self::settle_sale(&buyer, &seller, kitty_id, &kitty, sale_price, asset)
+        }
+
+        // 买家付款给卖家并转移kitty, 原生币成交计入统计
+        fn settle_sale(
+            buyer: &T::AccountId,
+            seller: &T::AccountId,
+            kitty_id: T::KittyIndex,
+            kitty: &Kitty<T>,
+            sale_price: BalanceOf<T>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+        ) -> DispatchResult {
+            match asset {
+                PaymentAsset::Native => {
+                    ensure!(
+                        T::Currency::free_balance(buyer) >= sale_price,
+                        Error::<T>::NotEnoughBalance
+                    );
+                    T::Currency::transfer(
+                        buyer,
+                        seller,
+                        sale_price,
+                        ExistenceRequirement::KeepAlive,
+                    )?;
+                }
+                PaymentAsset::Asset(asset_id) => {
+                    <T::Assets as fungibles::Transfer<T::AccountId>>::transfer(
+                        asset_id, buyer, seller, sale_price, true,
+                    )?;
+                }
+            }
+
+            Self::transfer_kitty_to(kitty_id, buyer, Some((sale_price, asset)))?;
+            // prices in different assets cannot be compared, only native sales make the stats.
+            if asset == PaymentAsset::Native {
+                Self::record_sale(kitty_id, &kitty.dna, sale_price);
+            }
+
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[buyer, seller],
+                Event::Bought {
+                    buyer: buyer.clone(),
+                    seller: seller.clone(),
+                    kitty_id,
+                    price: sale_price,
+                    asset,
+                },
+            );
+            Ok(())
+        }
+
+        fn list_kitty(
+            origin: OriginFor<T>,
+            kitty_id: T::KittyIndex,
+            price: Option<BalanceOf<T>>,
+            asset: PaymentAsset<AssetIdOf<T>>,
+        ) -> DispatchResult {
+            //此处注意, 必须先判断kitty_id是否存在, 否则无论kitty存在与否, 都会先报错NotOwner
+            let mut kitty = Self::kitty(kitty_id).ok_or(Error::<T>::InvalidKittyIndex)?;
+            let who = ensure_signed(origin)?;
+            Self::ensure_active(PauseScope::Trading)?;
+            ensure!(
+                Some(who.clone()) == Owner::<T>::get(kitty_id),
+                Error::<T>::NotOwner
+            );
+            Self::ensure_unlocked(kitty_id)?;
+            if price.is_some() {
+                Self::ensure_transferable(kitty_id)?;
+            }
+
+            let previous_price = kitty.price;
+            kitty.price = price;
+            Self::insert_kitty(kitty_id, kitty);
+            if let Some(previous_price) = previous_price {
+                ListingIndex::<T>::remove(Self::price_key(previous_price), kitty_id);
+            }
+            match price {
+                Some(price) if asset == PaymentAsset::Native => {
+                    ListingIndex::<T>::insert(Self::price_key(price), kitty_id, price);
+                    ListingAssets::<T>::remove(kitty_id);
+                }
+                Some(_) => ListingAssets::<T>::insert(kitty_id, asset),
+                None => ListingAssets::<T>::remove(kitty_id),
+            }
+            Self::deposit_event_indexed(
+                &[kitty_id],
+                &[&who],
+                Event::PriceUpdateForSale {
+                    owner: who.clone(),
+                    kitty_id,
+                    previous_price,
+                    price,
+                    asset: Self::listing_asset(kitty_id),
+                },
+            );
+            Ok(())
+        }
+
+        /// Topic under which every event about `kitty_id` is indexed.
+        pub fn kitty_topic(kitty_id: T::KittyIndex) -> T::Hash {
+            T::Hashing::hash_of(&(b"kitty", kitty_id))
+        }
+
+        /// Topic under which every kitty event involving `who` is indexed.
+        pub fn account_topic(who: &T::AccountId) -> T::Hash {
+            T::Hashing::hash_of(&(b"account", who))
+        }
+
+        // 按kitty和账户建立topic, 以便索引器只订阅某个kitty或账户的事件
+        fn deposit_event_indexed(
+            kitty_ids: &[T::KittyIndex],
+            accounts: &[&T::AccountId],
+            event: Event<T>,
+        ) {
+            let topics = kitty_ids
+                .iter()
+                .map(|kitty_id| Self::kitty_topic(*kitty_id))
+                .chain(accounts.iter().map(|who| Self::account_topic(who)))
+                .collect::<Vec<_>>();
+            let event = <T as Config>::Event::from(event);
+            frame_system::Pallet::<T>::deposit_event_indexed(&topics, event.into());
+        }
+
+        fn deposit_swap_event(swap: &SwapOf<T>, event: Event<T>) {
+            let kitty_ids = swap
+                .offered
+                .iter()
+                .chain(swap.requested.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            Self::deposit_event_indexed(&kitty_ids, &[&swap.proposer, &swap.counterparty], event);
+        }
+
+        /// The sub-account holding a kitty's funds, spendable only by its current owner.
+        pub fn kitty_wallet(kitty_id: T::KittyIndex) -> T::AccountId {
+            T::PalletId::get().into_sub_account(kitty_id)
+        }
+
+        /// A kitty by its index, whatever collection it belongs to.
+        pub fn kitty(kitty_id: T::KittyIndex) -> Option<Kitty<T>> {
+            Self::kitties(Self::collection_of(kitty_id), kitty_id)
+        }
+
+        fn insert_kitty(kitty_id: T::KittyIndex, kitty: Kitty<T>) {
+            Kitties::<T>::insert(Self::collection_of(kitty_id), kitty_id, kitty);
+        }
+
+        // 合集设置为不可转让的kitty不能转让, 出售或交换
+        // 灵魂绑定的kitty, 或者不可转让合集中的kitty, 只能由主人销毁
+        fn ensure_transferable(kitty_id: T::KittyIndex) -> Result<(), Error<T>> {
+            ensure!(
+                !Self::soulbound(kitty_id)
+                    && Self::collections(Self::collection_of(kitty_id))
+                        .map_or(true, |config| config.transferable),
+                Error::<T>::KittySoulbound
+            );
+            Ok(())
+        }
+
+        // 把旧的 Kitties(KittyIndex => Option<Kitty>) 迁移到合集0
+        fn migrate_to_collections() -> Weight {
+            let pallet_name = <Self as PalletInfoAccess>::name();
+            // collect first, so the drain never sees the entries written below.
+            let old_kitties: Vec<(T::KittyIndex, Option<Kitty<T>>)> = storage_key_iter::<
+                T::KittyIndex,
+                Option<Kitty<T>>,
+                Blake2_128Concat,
+            >(pallet_name.as_bytes(), b"Kitties")
+            .drain()
+            .collect();
+
+            let mut supply: u32 = 0;
+            for (kitty_id, kitty) in old_kitties.into_iter() {
+                if let Some(kitty) = kitty {
+                    Kitties::<T>::insert(0, kitty_id, kitty);
+                    supply += 1;
+                }
+            }
+            CollectionSupply::<T>::insert(0, supply);
+            StorageVersion::<T>::put(Releases::V2);
+
+            let n = supply as Weight;
+            T::DbWeight::get().reads_writes(1 + n, 2 + 2 * n)
+        }
+
+        // kitties minted before `KittyDeposits` and `TraitCounts` existed have neither. every
+        // trait is counted again, and the mint deposit of such a kitty is recorded as long as its
+        // owner still has it reserved, which used to be the case until its first transfer.
+        fn backfill_deposits_and_traits() -> Weight {
+            let deposit = T::MintDeposit::get();
+            // what is left of an owner's reserved balance once the known deposits are taken out.
+            let mut reserved: BTreeMap<T::AccountId, BalanceOf<T>> = BTreeMap::new();
+            let mut deposits: Weight = 0;
+            for (_, (depositor, amount)) in KittyDeposits::<T>::iter() {
+                deposits += 1;
+                let available = reserved
+                    .entry(depositor.clone())
+                    .or_insert_with(|| T::Currency::reserved_balance(&depositor));
+                *available = available.saturating_sub(amount);
+            }
+
+            let mut counts: BTreeMap<(u8, u8), u32> = BTreeMap::new();
+            let mut kitties: Weight = 0;
+            let mut recorded: Weight = 0;
+            for (_, kitty_id, kitty) in Kitties::<T>::iter() {
+                kitties += 1;
+                for (slot, variant) in Self::trait_variants(&kitty.dna).iter().enumerate() {
+                    *counts.entry((slot as u8, *variant)).or_insert(0) += 1;
+                }
+                if deposit.is_zero() || KittyDeposits::<T>::contains_key(kitty_id) {
+                    continue;
+                }
+                if let Some(owner) = Owner::<T>::get(kitty_id) {
+                    let available = reserved
+                        .entry(owner.clone())
+                        .or_insert_with(|| T::Currency::reserved_balance(&owner));
+                    if *available >= deposit {
+                        *available -= deposit;
+                        KittyDeposits::<T>::insert(kitty_id, (owner, deposit));
+                        recorded += 1;
+                    }
+                }
+            }
+
+            let stale: Vec<(u8, u8)> = TraitCounts::<T>::iter()
+                .map(|(slot, variant, _)| (slot, variant))
+                .filter(|key| !counts.contains_key(key))
+                .collect();
+            for (slot, variant) in stale.iter() {
+                TraitCounts::<T>::remove(slot, variant);
+            }
+            for ((slot, variant), count) in counts.iter() {
+                TraitCounts::<T>::insert(slot, variant, count);
+            }
+
+            let traits = (stale.len() + counts.len()) as Weight;
+            T::DbWeight::get().reads_writes(
+                2 * deposits + 3 * kitties + traits,
+                recorded + traits,
+            )
+        }
+
+        // indices were handed out without gaps, so everything minted but not alive was burned.
+        fn migrate_supply_counters() -> Weight {
+            let pallet_name = <Self as PalletInfoAccess>::name();
+            let minted: T::KittyIndex =
+                take_storage_value(pallet_name.as_bytes(), b"KittiesCount", &[])
+                    .unwrap_or_else(Zero::zero);
+            let mut collections: Weight = 0;
+            let live = CollectionSupply::<T>::iter_values().fold(0u32, |live, supply| {
+                collections += 1;
+                live.saturating_add(supply)
+            });
+            let live = T::KittyIndex::from(live);
+
+            NextKittyId::<T>::put(minted.saturating_add(One::one()));
+            TotalMinted::<T>::put(minted);
+            TotalBurned::<T>::put(minted.saturating_sub(live));
+            LiveSupply::<T>::put(live);
+            StorageVersion::<T>::put(Releases::V3);
+
+            T::DbWeight::get().reads_writes(1 + collections, 6)
+        }
+
+        // a child always has a higher index than its parents, so going through bred kitties in
+        // index order sees every parent's generation before its children.
+        fn migrate_generations() -> Weight {
+            let mut bred: Vec<(T::KittyIndex, (T::KittyIndex, T::KittyIndex))> =
+                Parents::<T>::iter().collect();
+            bred.sort_by_key(|(kitty_id, _)| *kitty_id);
+
+            // parents may be burned already, their generation is only kept here.
+            let mut generations = BTreeMap::new();
+            let mut written: Weight = 0;
+            for (kitty_id, (sire, dam)) in bred.iter() {
+                let generation_of = |id| generations.get(id).copied().unwrap_or(0u32);
+                let generation = generation_of(sire)
+                    .max(generation_of(dam))
+                    .saturating_add(1);
+                generations.insert(*kitty_id, generation);
+                if Owner::<T>::get(kitty_id).is_some() {
+                    Generation::<T>::insert(kitty_id, generation);
+                    written += 1;
+                }
+            }
+            StorageVersion::<T>::put(Releases::V4);
+
+            let n = bred.len() as Weight;
+            T::DbWeight::get().reads_writes(1 + 2 * n, 1 + written)
+        }
+
+        fn migrate_listing_index() -> Weight {
+            let mut kitties: Weight = 0;
+            let mut listed: Weight = 0;
+            for (_, kitty_id, kitty) in Kitties::<T>::iter() {
+                kitties += 1;
+                if let Some(price) = kitty.price {
+                    if Self::listing_asset(kitty_id) == PaymentAsset::Native {
+                        ListingIndex::<T>::insert(Self::price_key(price), kitty_id, price);
+                        listed += 1;
+                    }
+                }
+            }
+            StorageVersion::<T>::put(Releases::V5);
+
+            T::DbWeight::get().reads_writes(1 + 2 * kitties, 1 + listed)
+        }
+
+        /// Whether calls under `scope` are currently paused.
+        pub fn is_paused(scope: PauseScope) -> bool {
+            match Self::paused() {
+                PauseScope::Nothing => false,
+                PauseScope::All => true,
+                paused => paused == scope,
+            }
+        }
+
+        fn ensure_active(scope: PauseScope) -> Result<(), Error<T>> {
+            ensure!(!Self::is_paused(scope), Error::<T>::PalletPaused);
+            Ok(())
+        }
+
+        /// The scope a call is paused under, `None` for the privileged calls that never are.
+        pub fn pause_scope_of(call: &Call<T>) -> Option<PauseScope> {
+            match call {
+                Call::create_kitty(..)
+                | Call::create_collection(..)
+                | Call::create_kitty_in_collection(..) => Some(PauseScope::Minting),
+                Call::breed(..) => Some(PauseScope::Breeding),
+                Call::transfer(..)
+                | Call::relay_transfer(..)
+                | Call::offer_transfer(..)
+                | Call::claim_transfer(..)
+                | Call::sell_kitty(..)
+                | Call::sell_kitty_for_asset(..)
+                | Call::buy_kitty(..)
+                | Call::buy_cheapest(..)
+                | Call::fill_order(..)
+                | Call::lend(..)
+                | Call::accept_loan(..)
+                | Call::propose_swap(..)
+                | Call::accept_swap(..)
+                | Call::list_bundle(..)
+                | Call::buy_bundle(..)
+                | Call::kitty_wallet_transfer(..) => Some(PauseScope::Trading),
+                Call::set_requires_acceptance(..)
+                | Call::reject_transfer(..)
+                | Call::burn(..)
+                | Call::cancel_swap(..)
+                | Call::cancel_loan(..)
+                | Call::cancel_bundle(..)
+                | Call::cancel_order_nonce(..)
+                | Call::destroy_collection(..) => Some(PauseScope::All),
+                _ => None,
+            }
+        }
+
+        /// Whether a call may be dispatched under the current pause state, used by the runtime's
+        /// transaction validation to keep paused calls out of the pool.
+        pub fn is_call_allowed(call: &Call<T>) -> bool {
+            Self::pause_scope_of(call).map_or(true, |scope| !Self::is_paused(scope))
+        }
+
+        fn ensure_not_frozen(kitty_id: T::KittyIndex) -> Result<(), Error<T>> {
+            ensure!(!Self::frozen(kitty_id), Error::<T>::KittyFrozen);
+            Ok(())
+        }
+
+        fn ensure_no_locks(kitty_id: T::KittyIndex) -> Result<(), Error<T>> {
+            ensure!(
+                !KittyLocks::<T>::contains_key(kitty_id),
+                Error::<T>::KittyLocked
+            );
+            Ok(())
+        }
+
+        // 被冻结, 被出租, 被托管或者等待对方确认转让的kitty不能转移
+        fn ensure_unlocked(kitty_id: T::KittyIndex) -> Result<(), Error<T>> {
+            Self::ensure_not_frozen(kitty_id)?;
+            Self::ensure_no_locks(kitty_id)?;
+            ensure!(
+                !Loans::<T>::contains_key(kitty_id),
+                Error::<T>::KittyOnLoan
+            );
+            ensure!(
+                !SwapEscrow::<T>::contains_key(kitty_id),
+                Error::<T>::KittyInEscrow
+            );
+            ensure!(
+                !BundledKitties::<T>::contains_key(kitty_id),
+                Error::<T>::KittyInBundle
+            );
+            ensure!(
+                !PendingTransfers::<T>::contains_key(kitty_id),
+                Error::<T>::KittyTransferPending
+            );
+            Ok(())
+        }
+
+        fn ensure_distinct(kitty_ids: &[T::KittyIndex]) -> Result<(), Error<T>> {
+            let mut sorted = kitty_ids.to_vec();
+            sorted.sort();
+            sorted.dedup();
+            ensure!(sorted.len() == kitty_ids.len(), Error::<T>::DuplicateKitty);
+            Ok(())
+        }
+
+        // 取消kitty上的出租, 交换, bundle, 待确认转让以及挂单
+        fn clear_encumbrances(kitty_id: T::KittyIndex) {
+            Loans::<T>::remove(kitty_id);
+            PendingTransfers::<T>::remove(kitty_id);
+            if let Some(swap_id) = SwapEscrow::<T>::get(kitty_id) {
+                if let Some(swap) = Swaps::<T>::get(swap_id) {
+                    Self::close_swap(swap_id, &swap);
+                    if let Some(amount) = swap.sweetener {
+                        T::Currency::unreserve(&swap.proposer, amount);
+                    }
+                    Self::deposit_swap_event(&swap, Event::SwapCancelled { swap_id });
+                }
+            }
+            if let Some(bundle_id) = BundledKitties::<T>::get(kitty_id) {
+                if let Some(bundle) = Bundles::<T>::get(bundle_id) {
+                    Self::close_bundle(bundle_id, &bundle);
+                    Self::deposit_event_indexed(
+                        &bundle.kitties,
+                        &[&bundle.seller],
+                        Event::BundleCancelled { bundle_id },
+                    );
+                }
+            }
+            Self::delist(kitty_id);
+        }
+
+        fn close_bundle(bundle_id: BundleId, bundle: &BundleOf<T>) {
+            for kitty_id in bundle.kitties.iter() {
+                BundledKitties::<T>::remove(kitty_id);
+            }
+            Bundles::<T>::remove(bundle_id);
+        }
+
+        // 撤下挂单, 同时从价格索引中移除; kitty被出租, 托管, 冻结, 加锁或者等待确认转让时也会调用,
+        // 这样挂单和价格索引里只会有能直接买到的kitty
+        fn delist(kitty_id: T::KittyIndex) {
+            Kitties::<T>::mutate(Self::collection_of(kitty_id), kitty_id, |kitty| {
+                if let Some(price) = kitty.as_mut().and_then(|kitty| kitty.price.take()) {
+                    ListingIndex::<T>::remove(Self::price_key(price), kitty_id);
+                }
+            });
+            ListingAssets::<T>::remove(kitty_id);
+        }
+
+        // 链下签名的消息里带上创世区块哈希, 签名不能拿到别的链上重放
+        fn genesis_hash() -> T::Hash {
+            <frame_system::Pallet<T>>::block_hash(T::BlockNumber::zero())
+        }
+
+        fn price_key(price: BalanceOf<T>) -> [u8; 16] {
+            let price: u128 = price.unique_saturated_into();
+            price.to_be_bytes()
+        }
+
+        /// The `n` cheapest kitties listed in the native currency, cheapest first.
+        pub fn cheapest_listings(n: u32) -> Vec<(T::KittyIndex, BalanceOf<T>)> {
+            ListingIndex::<T>::iter()
+                .take(n as usize)
+                .map(|(_, kitty_id, price)| (kitty_id, price))
+                .collect()
+        }
+
+        fn close_swap(swap_id: SwapId, swap: &SwapOf<T>) {
+            for kitty_id in swap.offered.iter() {
+                SwapEscrow::<T>::remove(kitty_id);
+            }
+            Swaps::<T>::remove(swap_id);
+        }
+
+        /// Whether `who` may use a kitty, e.g. for breeding: the borrower while an accepted loan
+        /// runs, the owner otherwise.
+        pub fn can_use(who: &T::AccountId, kitty_id: T::KittyIndex) -> bool {
+            match Loans::<T>::get(kitty_id) {
+                Some(loan) if loan.accepted => loan.borrower == *who,
+                _ => Owner::<T>::get(kitty_id).as_ref() == Some(who),
+            }
+        }
+
+        // 禁止父母与子女, 以及兄弟姐妹(包括同父异母/同母异父)之间繁殖
+        fn ensure_not_related(
+            kitty_id_1: T::KittyIndex,
+            kitty_id_2: T::KittyIndex,
+        ) -> Result<(), Error<T>> {
+            let parents_1 = Self::parents(kitty_id_1);
+            let parents_2 = Self::parents(kitty_id_2);
+            if let Some((sire, dam)) = parents_1 {
+                ensure!(
+                    sire != kitty_id_2 && dam != kitty_id_2,
+                    Error::<T>::Inbreeding
+                );
+            }
+            if let Some((sire, dam)) = parents_2 {
+                ensure!(
+                    sire != kitty_id_1 && dam != kitty_id_1,
+                    Error::<T>::Inbreeding
+                );
+            }
+            if let (Some((sire_1, dam_1)), Some((sire_2, dam_2))) = (parents_1, parents_2) {
+                ensure!(
+                    sire_1 != sire_2 && dam_1 != dam_2,
+                    Error::<T>::Inbreeding
+                );
+            }
+            Ok(())
+        }
+
+        /// The mint phase at the current block, following `MintSchedule`.
+        pub fn mint_phase() -> MintPhase {
+            let now = <frame_system::Pallet<T>>::block_number();
+            match Self::mint_schedule() {
+                None => MintPhase::Public,
+                Some((_, public_start)) if now >= public_start => MintPhase::Public,
+                Some((presale_start, _)) if now >= presale_start => MintPhase::Presale,
+                Some(_) => MintPhase::Closed,
+            }
+        }
+
+        // 检查铸造频率, 合集0还要检查gen0总量上限以及铸造阶段, 返回当前窗口以及该窗口内已铸造的数量
+        fn ensure_can_mint_gen0(
+            who: &T::AccountId,
+            collection_id: CollectionId,
+        ) -> Result<(T::BlockNumber, u32), DispatchError> {
+            Self::ensure_gen0_supply_left(collection_id)?;
+            let (window_start, minted) = Self::current_mint_window(who);
+            ensure!(
+                minted < T::MaxMintsPerWindow::get(),
+                Error::<T>::MintRateLimited
+            );
+
+            if collection_id == 0 {
+                match Self::mint_phase() {
+                    MintPhase::Closed => return Err(Error::<T>::MintClosed.into()),
+                    MintPhase::Presale => Self::presale_mint(who)?,
+                    MintPhase::Public => {}
+                }
+            }
+            Ok((window_start, minted))
+        }
+
+        fn note_gen0_mint(
+            who: &T::AccountId,
+            collection_id: CollectionId,
+            window_start: T::BlockNumber,
+            minted: u32,
+        ) {
+            Self::note_gen0_supply(collection_id);
+            MintWindows::<T>::insert(who, (window_start, minted + 1));
+        }
+
+        // 只有合集0计入gen0总量, 合集有自己的供应上限
+        fn ensure_gen0_supply_left(collection_id: CollectionId) -> Result<(), Error<T>> {
+            if collection_id == 0 {
+                ensure!(
+                    Self::gen0_supply() < T::MaxGen0Supply::get(),
+                    Error::<T>::Gen0SupplyExhausted
+                );
+            }
+            Ok(())
+        }
+
+        fn note_gen0_supply(collection_id: CollectionId) {
+            if collection_id == 0 {
+                Gen0Supply::<T>::mutate(|supply| *supply = supply.saturating_add(1));
+            }
+        }
+
+        // 预售期间只有白名单账户可以铸造, 每个账户有数量限制, 并可能需要支付预售价格
+        fn presale_mint(who: &T::AccountId) -> DispatchResult {
+            ensure!(Self::allowlisted(who), Error::<T>::NotAllowlisted);
+
+            let terms = Self::presale_terms().ok_or(Error::<T>::PresaleQuotaExceeded)?;
+            let minted = Self::presale_minted(who);
+            ensure!(minted < terms.quota, Error::<T>::PresaleQuotaExceeded);
+
+            if let Some((price, payee)) = terms.price {
+                T::Currency::transfer(who, &payee, price, ExistenceRequirement::KeepAlive)?;
+            }
+            PresaleMinted::<T>::insert(who, minted + 1);
             Ok(())
         }
+
+        // 返回当前窗口的起始区块, 以及该账户在这个窗口内已经铸造的数量
+        fn current_mint_window(who: &T::AccountId) -> (T::BlockNumber, u32) {
+            let now = <frame_system::Pallet<T>>::block_number();
+            let window = T::MintWindow::get();
+            let window_start = if window.is_zero() {
+                now
+            } else {
+                now - now % window
+            };
+            match Self::mint_window_of(who) {
+                Some((start, minted)) if start == window_start => (window_start, minted),
+                _ => (window_start, 0),
+            }
+        }
+
+        fn charge_breeding_fee(who: &T::AccountId) -> DispatchResult {
+            let EconomicParameters {
+                breeding_fee,
+                fee_destination,
+                ..
+            } = Self::parameters();
+            if breeding_fee.is_zero() {
+                return Ok(());
+            }
+            match fee_destination {
+                Some(dest) => {
+                    T::Currency::transfer(who, &dest, breeding_fee, ExistenceRequirement::KeepAlive)
+                }
+                None => T::Currency::withdraw(
+                    who,
+                    breeding_fee,
+                    WithdrawReasons::FEE,
+                    ExistenceRequirement::KeepAlive,
+                )
+                .map(drop),
+            }
+        }
+
+        fn release_deposit(kitty_id: T::KittyIndex) {
+            if let Some((depositor, deposit)) = KittyDeposits::<T>::take(kitty_id) {
+                T::Currency::unreserve(&depositor, deposit);
+            }
+        }
+
+        // 记录成交价, 更新当前窗口的成交量以及代数和主特征的地板价
+        fn record_sale(kitty_id: T::KittyIndex, dna: &[u8; 16], price: BalanceOf<T>) {
+            LastSalePrice::<T>::insert(kitty_id, price);
+            Self::record_volume(price);
+
+            let window_start = Self::current_volume_window();
+            let lower_floor = |floor: &mut Option<(T::BlockNumber, BalanceOf<T>)>| {
+                let still_lowest = matches!(floor, Some((start, lowest))
+                    if *start == window_start && *lowest <= price);
+                if !still_lowest {
+                    *floor = Some((window_start, price));
+                }
+            };
+            GenerationFloor::<T>::mutate(Self::generation(kitty_id), lower_floor);
+            TraitFloor::<T>::mutate(Self::dominant_trait(dna), lower_floor);
+        }
+
+        // 计入当前窗口的成交量
+        fn record_volume(price: BalanceOf<T>) {
+            let window_start = Self::current_volume_window();
+            Volume::<T>::mutate(|volume| {
+                if volume.window_start != window_start {
+                    let follows = volume.window_start + T::VolumeWindow::get() == window_start;
+                    let previous = if follows {
+                        volume.current
+                    } else {
+                        Zero::zero()
+                    };
+                    *volume = SaleVolume {
+                        window_start,
+                        current: Zero::zero(),
+                        previous,
+                    };
+                }
+                volume.current = volume.current.saturating_add(price);
+            });
+        }
+
+        fn current_volume_window() -> T::BlockNumber {
+            let now = <frame_system::Pallet<T>>::block_number();
+            let window = T::VolumeWindow::get();
+            if window.is_zero() {
+                now
+            } else {
+                now - now % window
+            }
+        }
+
+        /// Native-currency sale volume of the current and the previous `VolumeWindow`.
+        pub fn rolling_volume() -> BalanceOf<T> {
+            let volume = Self::sale_volume();
+            let window_start = Self::current_volume_window();
+            if volume.window_start == window_start {
+                volume.current.saturating_add(volume.previous)
+            } else if volume.window_start + T::VolumeWindow::get() == window_start {
+                volume.current
+            } else {
+                Zero::zero()
+            }
+        }
+
+        /// The trait variant found in most slots of a kitty, the lowest one on a tie.
+        pub fn dominant_trait(dna: &[u8; 16]) -> u8 {
+            let mut counts = [0u8; 16];
+            for variant in Self::trait_variants(dna).iter() {
+                counts[*variant as usize] += 1;
+            }
+            let mut dominant = 0;
+            for (variant, count) in counts.iter().enumerate() {
+                if *count > counts[dominant] {
+                    dominant = variant;
+                }
+            }
+            dominant as u8
+        }
+
+        /// The variant of every trait slot: the high nibble of the matching dna byte.
+        pub fn trait_variants(dna: &[u8; 16]) -> [u8; TRAIT_SLOTS] {
+            let mut variants = [0u8; TRAIT_SLOTS];
+            for (variant, byte) in variants.iter_mut().zip(dna.iter()) {
+                *variant = byte >> 4;
+            }
+            variants
+        }
+
+        fn add_traits(dna: &[u8; 16]) {
+            for (slot, variant) in Self::trait_variants(dna).iter().enumerate() {
+                TraitCounts::<T>::mutate(slot as u8, variant, |count| {
+                    *count = count.saturating_add(1)
+                });
+            }
+        }
+
+        fn remove_traits(dna: &[u8; 16]) {
+            for (slot, variant) in Self::trait_variants(dna).iter().enumerate() {
+                TraitCounts::<T>::mutate(slot as u8, variant, |count| {
+                    *count = count.saturating_sub(1)
+                });
+            }
+        }
+
+        /// Rarity score of a kitty, `None` if it does not exist.
+        ///
+        /// Every trait slot contributes `RARITY_SCALE / n`, where `n` is the number of live
+        /// kitties sharing that trait, so kitties with uncommon traits score higher.
+        pub fn rarity_of(kitty_id: T::KittyIndex) -> Option<u64> {
+            let kitty = Self::kitty(kitty_id)?;
+            let score: u64 = Self::trait_variants(&kitty.dna)
+                .iter()
+                .enumerate()
+                .map(|(slot, variant)| {
+                    let count = TraitCounts::<T>::get(slot as u8, variant).max(1);
+                    RARITY_SCALE / count as u64
+                })
+                .sum();
+            Some(score)
+        }
+    }
+
+    impl<T: Config> KittyLock<T::KittyIndex> for Pallet<T> {
+        fn set_lock(kitty_id: T::KittyIndex, reason: LockReason) -> DispatchResult {
+            ensure!(
+                Owner::<T>::get(kitty_id).is_some(),
+                Error::<T>::InvalidKittyIndex
+            );
+            KittyLocks::<T>::try_mutate(kitty_id, |locks| -> DispatchResult {
+                if let Err(index) = locks.binary_search(&reason) {
+                    locks.insert(index, reason);
+                    Self::delist(kitty_id);
+                    Self::deposit_event_indexed(
+                        &[kitty_id],
+                        &[],
+                        Event::KittyLockSet { kitty_id, reason },
+                    );
+                }
+                Ok(())
+            })
+        }
+
+        fn remove_lock(kitty_id: T::KittyIndex, reason: LockReason) {
+            KittyLocks::<T>::mutate_exists(kitty_id, |maybe_locks| {
+                if let Some(locks) = maybe_locks {
+                    if let Ok(index) = locks.binary_search(&reason) {
+                        locks.remove(index);
+                        Self::deposit_event_indexed(
+                            &[kitty_id],
+                            &[],
+                            Event::KittyLockRemoved { kitty_id, reason },
+                        );
+                    }
+                    if locks.is_empty() {
+                        *maybe_locks = None;
+                    }
+                }
+            });
+        }
+
+        fn locks(kitty_id: T::KittyIndex) -> Vec<LockReason> {
+            Self::kitty_locks(kitty_id)
+        }
     }
 }
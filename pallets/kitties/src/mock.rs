@@ -1,11 +1,18 @@
 use crate as pallet_kitties;
-use frame_support::parameter_types;
+use crate::KittyLifecycleHooks;
+use codec::{Decode, Encode};
+use frame_support::{
+    parameter_types,
+    traits::{GenesisBuild, OnInitialize},
+    PalletId,
+};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
     testing::Header,
-    traits::{BlakeTwo256, IdentityLookup},
+    traits::{BlakeTwo256, IdentifyAccount, IdentityLookup, Lazy, Verify},
 };
+use std::cell::RefCell;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
@@ -20,7 +27,8 @@ frame_support::construct_runtime!(
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Pallet, Storage},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
-        KittiesModule: pallet_kitties::{Pallet, Call, Storage, Event<T>},
+        Assets: pallet_assets::{Pallet, Call, Storage, Event<T>},
+        KittiesModule: pallet_kitties::{Pallet, Call, Config, Storage, Event<T>},
     }
 );
 
@@ -40,7 +48,9 @@ impl system::Config for Test {
     type BlockNumber = u64;
     type Hash = H256;
     type Hashing = BlakeTwo256;
-    type AccountId = u64;
+    // kitty wallets are `modl` + pallet id + kitty index, 16 bytes, so account ids must be at
+    // least as wide for every kitty to get its own wallet.
+    type AccountId = u128;
     type Lookup = IdentityLookup<Self::AccountId>;
     type Header = Header;
     type Event = Event;
@@ -57,6 +67,15 @@ impl system::Config for Test {
 // 最少预留押金为20
 parameter_types! {
   pub const MintDepositAmount: u128 = 20;
+  pub const MaxSwapKitties: u32 = 4;
+  pub const MaxGen0Supply: u32 = 4;
+  pub const MintWindow: u64 = 10;
+  pub const MaxMintsPerWindow: u32 = 3;
+  pub const KittiesPalletId: PalletId = PalletId(*b"py/kitty");
+  pub const MaxProvenance: u32 = 2;
+  pub const VolumeWindow: u64 = 10;
+  pub const MaxBundleKitties: u32 = 3;
+  pub const MaxLoanPeriod: u64 = 20;
 }
 
 impl pallet_kitties::Config for Test {
@@ -65,6 +84,82 @@ impl pallet_kitties::Config for Test {
     type Currency = Balances;
     type KittyIndex = u32;
     type MintDeposit = MintDepositAmount;
+    type MaxSwapKitties = MaxSwapKitties;
+    type ForceOrigin = frame_system::EnsureRoot<u128>;
+    type PauseOrigin = frame_system::EnsureRoot<u128>;
+    type ParametersOrigin = frame_system::EnsureRoot<u128>;
+    type MaxGen0Supply = MaxGen0Supply;
+    type MintWindow = MintWindow;
+    type MaxMintsPerWindow = MaxMintsPerWindow;
+    type MintAdminOrigin = frame_system::EnsureRoot<u128>;
+    type Assets = Assets;
+    type PalletId = KittiesPalletId;
+    type OnKittyChange = (RecordKittyChanges,);
+    type MaxProvenance = MaxProvenance;
+    type VolumeWindow = VolumeWindow;
+    type MaxBundleKitties = MaxBundleKitties;
+    type OffchainSignature = TestSignature;
+    type OffchainPublic = TestSigner;
+    type MaxLoanPeriod = MaxLoanPeriod;
+}
+
+// 链下签名: 签名者以及被签名的消息, 和 `sp_runtime::testing::TestSignature` 一样,
+// 只是账户是 u128
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSignature(pub u128, pub Vec<u8>);
+
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+pub struct TestSigner(pub u128);
+
+impl IdentifyAccount for TestSigner {
+    type AccountId = u128;
+
+    fn into_account(self) -> u128 {
+        self.0
+    }
+}
+
+impl Verify for TestSignature {
+    type Signer = TestSigner;
+
+    fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u128) -> bool {
+        self.0 == *signer && msg.get() == &self.1[..]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KittyChange {
+    Minted(u128, u32),
+    Transferred(u128, u128, u32),
+    Burned(u128, u32),
+}
+
+thread_local! {
+    static KITTY_CHANGES: RefCell<Vec<KittyChange>> = RefCell::new(vec![]);
+}
+
+// 记录kitty生命周期回调, 供测试检查
+pub struct RecordKittyChanges;
+
+impl KittyLifecycleHooks<u128, u32> for RecordKittyChanges {
+    fn on_mint(owner: &u128, kitty_id: u32) {
+        KITTY_CHANGES.with(|c| c.borrow_mut().push(KittyChange::Minted(*owner, kitty_id)));
+    }
+
+    fn on_transfer(from: &u128, to: &u128, kitty_id: u32) {
+        KITTY_CHANGES.with(|c| {
+            c.borrow_mut()
+                .push(KittyChange::Transferred(*from, *to, kitty_id))
+        });
+    }
+
+    fn on_burn(owner: &u128, kitty_id: u32) {
+        KITTY_CHANGES.with(|c| c.borrow_mut().push(KittyChange::Burned(*owner, kitty_id)));
+    }
+}
+
+pub fn kitty_changes() -> Vec<KittyChange> {
+    KITTY_CHANGES.with(|c| c.borrow().clone())
 }
 
 parameter_types! {
@@ -83,6 +178,30 @@ impl pallet_balances::Config for Test {
     type WeightInfo = ();
 }
 
+parameter_types! {
+  pub const AssetDeposit: u128 = 1;
+  pub const ApprovalDeposit: u128 = 1;
+  pub const StringLimit: u32 = 50;
+  pub const MetadataDepositBase: u128 = 1;
+  pub const MetadataDepositPerByte: u128 = 1;
+}
+
+impl pallet_assets::Config for Test {
+    type Event = Event;
+    type Balance = u128;
+    type AssetId = u32;
+    type Currency = Balances;
+    type ForceOrigin = frame_system::EnsureRoot<u128>;
+    type AssetDeposit = AssetDeposit;
+    type MetadataDepositBase = MetadataDepositBase;
+    type MetadataDepositPerByte = MetadataDepositPerByte;
+    type ApprovalDeposit = ApprovalDeposit;
+    type StringLimit = StringLimit;
+    type Freezer = ();
+    type Extra = ();
+    type WeightInfo = ();
+}
+
 impl pallet_randomness_collective_flip::Config for Test {}
 
 // Build genesis storage according to the mock runtime.
@@ -111,8 +230,19 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
     }
     .assimilate_storage(&mut t)
     .unwrap();
+    GenesisBuild::<Test>::assimilate_storage(&pallet_kitties::GenesisConfig::default(), &mut t)
+        .unwrap();
     //此处一小坑,必须设置初始区块数, 否则randomness_collective_flip模块测试时会报错溢出
+    KITTY_CHANGES.with(|c| c.borrow_mut().clear());
     let mut ext = sp_io::TestExternalities::new(t);
     ext.execute_with(|| System::set_block_number(1));
     ext
 }
+
+// 推进区块并执行kitties模块的on_initialize
+pub fn run_to_block(n: u64) {
+    while System::block_number() < n {
+        System::set_block_number(System::block_number() + 1);
+        KittiesModule::on_initialize(System::block_number());
+    }
+}
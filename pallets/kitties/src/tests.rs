@@ -1,6 +1,29 @@
 use super::*;
 use crate::{mock::*, Error};
-use frame_support::{assert_noop, assert_ok};
+use codec::Encode;
+use frame_support::{
+    assert_noop, assert_ok,
+    storage::migration::put_storage_value,
+    traits::{OnRuntimeUpgrade, ReservableCurrency},
+    Blake2_128Concat, StorageHasher,
+};
+
+// 按序号引用kitty
+fn kitty_ref(kitty_id: u32) -> KittyRefOf<Test> {
+    KittyRef::Index(kitty_id)
+}
+
+// overwrite the gender bit of a kitty's dna, since kitties minted in tests share the same dna.
+fn set_gender(kitty_id: u32, gender: Gender) {
+    Kitties::<Test>::mutate(KittiesModule::collection_of(kitty_id), kitty_id, |kitty| {
+        if let Some(kitty) = kitty {
+            kitty.dna[0] = match gender {
+                Gender::Male => 0,
+                Gender::Female => 1,
+            };
+        }
+    });
+}
 
 #[test]
 fn create_a_kitty_works_when_have_enough_balance() {
@@ -25,8 +48,16 @@ fn breed_a_kitty_works_when_balance_enough() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
         assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
 
-        assert_ok!(KittiesModule::breed(Origin::signed(8), 1, 2),);
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        assert_eq!(KittiesModule::parents(3), Some((1, 2)));
+        assert_eq!(KittiesModule::generation(3), 1);
     });
 }
 
@@ -36,9 +67,11 @@ fn breed_a_kitty_fails_when_balance_not_enough() {
         // count 4 have enough balance to create 2 kitty, but not enough to bread a new one after then.
         assert_ok!(KittiesModule::create_kitty(Origin::signed(4)),);
         assert_ok!(KittiesModule::create_kitty(Origin::signed(4)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
 
         assert_noop!(
-            KittiesModule::breed(Origin::signed(4), 1, 2),
+            KittiesModule::breed(Origin::signed(4), kitty_ref(1), kitty_ref(2)),
             Error::<Test>::NotEnoughBalance
         );
     });
@@ -51,7 +84,7 @@ fn breed_a_kitty_fails_when_parent_invalid() {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(4)),);
 
         assert_noop!(
-            KittiesModule::breed(Origin::signed(4), 1, 22),
+            KittiesModule::breed(Origin::signed(4), kitty_ref(1), kitty_ref(22)),
             Error::<Test>::InvalidKittyIndex
         );
     });
@@ -62,17 +95,80 @@ fn breed_a_kitty_fails_when_parents_are_same() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(4)),);
         assert_noop!(
-            KittiesModule::breed(Origin::signed(4), 1, 1),
+            KittiesModule::breed(Origin::signed(4), kitty_ref(1), kitty_ref(1)),
             Error::<Test>::SameParentIndex
         );
     });
 }
 
+#[test]
+fn breed_a_kitty_fails_when_parents_have_same_gender() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Female);
+        set_gender(2, Gender::Female);
+
+        assert_noop!(
+            KittiesModule::breed(Origin::signed(8), kitty_ref(1), kitty_ref(2)),
+            Error::<Test>::IncompatibleParents
+        );
+    });
+}
+
+#[test]
+fn breed_a_kitty_fails_when_parent_and_child() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        set_gender(3, Gender::Female);
+
+        assert_noop!(
+            KittiesModule::breed(Origin::signed(8), kitty_ref(1), kitty_ref(3)),
+            Error::<Test>::Inbreeding
+        );
+    });
+}
+
+#[test]
+fn breed_a_kitty_fails_when_siblings() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        set_gender(3, Gender::Male);
+        set_gender(4, Gender::Female);
+
+        assert_noop!(
+            KittiesModule::breed(Origin::signed(8), kitty_ref(3), kitty_ref(4)),
+            Error::<Test>::Inbreeding
+        );
+    });
+}
+
 #[test]
 fn transfer_a_kitty_works() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
-        assert_ok!(KittiesModule::transfer(Origin::signed(3), 1, 1));
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 1, kitty_ref(1)));
         assert_eq!(Owner::<Test>::get(1), Some(1));
     });
 }
@@ -82,7 +178,7 @@ fn transfer_a_kitty_fails_when_not_owner() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
         assert_noop!(
-            KittiesModule::transfer(Origin::signed(2), 1, 1),
+            KittiesModule::transfer(Origin::signed(2), 1, kitty_ref(1)),
             Error::<Test>::NotOwner
         );
     });
@@ -92,7 +188,11 @@ fn transfer_a_kitty_fails_when_not_owner() {
 fn sell_a_kitty_works() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
-        assert_ok!(KittiesModule::sell_kitty(Origin::signed(3), 1, Some(20)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(20)
+        ));
     });
 }
 
@@ -101,7 +201,7 @@ fn sell_a_kitty_fails_when_not_owner() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
         assert_noop!(
-            KittiesModule::sell_kitty(Origin::signed(1), 1, Some(20)),
+            KittiesModule::sell_kitty(Origin::signed(1), kitty_ref(1), Some(20)),
             Error::<Test>::NotOwner
         );
     });
@@ -112,7 +212,7 @@ fn sell_a_kitty_fails_when_kitty_index_invalid() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
         assert_noop!(
-            KittiesModule::sell_kitty(Origin::signed(3), 121, Some(20)),
+            KittiesModule::sell_kitty(Origin::signed(3), kitty_ref(121), Some(20)),
             Error::<Test>::InvalidKittyIndex
         );
     });
@@ -122,8 +222,17 @@ fn sell_a_kitty_fails_when_kitty_index_invalid() {
 fn buy_a_kitty_works() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
-        assert_ok!(KittiesModule::sell_kitty(Origin::signed(3), 1, Some(9)));
-        assert_ok!(KittiesModule::buy_kitty(Origin::signed(1), 1));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(1),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            9
+        ));
         assert_eq!(Owner::<Test>::get(1), Some(1));
     });
 }
@@ -132,9 +241,13 @@ fn buy_a_kitty_works() {
 fn buy_a_kitty_fails_when_buyer_is_kitty_owner() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
-        assert_ok!(KittiesModule::sell_kitty(Origin::signed(3), 1, Some(9)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
         assert_noop!(
-            KittiesModule::buy_kitty(Origin::signed(3), 1),
+            KittiesModule::buy_kitty(Origin::signed(3), kitty_ref(1), PaymentAsset::Native, 9),
             Error::<Test>::BuyerIsKittyOwner
         );
     });
@@ -144,9 +257,13 @@ fn buy_a_kitty_fails_when_buyer_is_kitty_owner() {
 fn buy_a_kitty_fails_when_not_enough_balance() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
-        assert_ok!(KittiesModule::sell_kitty(Origin::signed(3), 1, Some(19)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(19)
+        ));
         assert_noop!(
-            KittiesModule::buy_kitty(Origin::signed(1), 1),
+            KittiesModule::buy_kitty(Origin::signed(1), kitty_ref(1), PaymentAsset::Native, 19),
             Error::<Test>::NotEnoughBalance
         );
     });
@@ -157,7 +274,7 @@ fn buy_a_kitty_fails_when_not_for_sell() {
     new_test_ext().execute_with(|| {
         assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
         assert_noop!(
-            KittiesModule::buy_kitty(Origin::signed(1), 1),
+            KittiesModule::buy_kitty(Origin::signed(1), kitty_ref(1), PaymentAsset::Native, 9),
             Error::<Test>::KittyNotForSale
         );
     });
@@ -167,8 +284,2037 @@ fn buy_a_kitty_fails_when_not_for_sell() {
 fn buy_a_kitty_fails_when_kitty_not_exist() {
     new_test_ext().execute_with(|| {
         assert_noop!(
-            KittiesModule::buy_kitty(Origin::signed(1), 1),
+            KittiesModule::buy_kitty(Origin::signed(1), kitty_ref(1), PaymentAsset::Native, 9),
             Error::<Test>::InvalidKittyIndex
         );
     });
 }
+
+#[test]
+fn buy_a_kitty_with_asset_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(Assets::force_create(Origin::root(), 0, 1, true, 1));
+        assert_ok!(Assets::mint(Origin::signed(1), 0, 2, 100));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::sell_kitty_for_asset(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(40),
+            PaymentAsset::Asset(0)
+        ));
+        assert_eq!(KittiesModule::listing_asset(1), PaymentAsset::Asset(0));
+
+        // 价格高于原生币余额, 但以资产支付
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(2),
+            kitty_ref(1),
+            PaymentAsset::Asset(0),
+            40
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(2));
+        assert_eq!(Assets::balance(0, 2), 60);
+        assert_eq!(Assets::balance(0, 3), 40);
+        assert_eq!(Balances::free_balance(2), 20);
+    });
+}
+
+#[test]
+fn buy_a_kitty_fails_when_the_listing_changed() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(12)
+        ));
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(4), kitty_ref(1), PaymentAsset::Native, 9),
+            Error::<Test>::PriceTooHigh
+        );
+        assert_ok!(KittiesModule::sell_kitty_for_asset(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(5),
+            PaymentAsset::Asset(0)
+        ));
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(4), kitty_ref(1), PaymentAsset::Native, 9),
+            Error::<Test>::PaymentAssetMismatch
+        );
+
+        // the buyer pays the listed price, not the most they were willing to pay.
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(7)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(4),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            9
+        ));
+        assert_eq!(Balances::free_balance(4), 40 - 7);
+    });
+}
+
+#[test]
+fn relisting_a_kitty_in_native_currency_clears_asset() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::sell_kitty_for_asset(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(40),
+            PaymentAsset::Asset(0)
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_eq!(KittiesModule::listing_asset(1), PaymentAsset::Native);
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(1),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            9
+        ));
+        assert_eq!(Balances::free_balance(3), 30 + 9);
+    });
+}
+
+#[test]
+fn burn_a_kitty_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_eq!(Balances::reserved_balance(3), 20);
+        assert_ok!(KittiesModule::burn(Origin::signed(3), kitty_ref(1)));
+        assert_eq!(Owner::<Test>::get(1), None);
+        assert_eq!(KittiesModule::kitty(1), None);
+        assert_eq!(Balances::reserved_balance(3), 0);
+    });
+}
+
+#[test]
+fn burn_a_kitty_fails_when_not_owner() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::burn(Origin::signed(2), kitty_ref(1)),
+            Error::<Test>::NotOwner
+        );
+    });
+}
+
+#[test]
+fn rarity_follows_trait_frequencies() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_eq!(
+            KittiesModule::rarity_of(1),
+            Some(TRAIT_SLOTS as u64 * RARITY_SCALE)
+        );
+
+        // the same account minting in the same block gets the same dna, so every trait is shared.
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_eq!(
+            KittiesModule::rarity_of(1),
+            Some(TRAIT_SLOTS as u64 * RARITY_SCALE / 2)
+        );
+
+        assert_ok!(KittiesModule::burn(Origin::signed(8), kitty_ref(2)));
+        assert_eq!(
+            KittiesModule::rarity_of(1),
+            Some(TRAIT_SLOTS as u64 * RARITY_SCALE)
+        );
+        assert_eq!(KittiesModule::rarity_of(2), None);
+    });
+}
+
+#[test]
+fn lend_a_kitty_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_ok!(KittiesModule::accept_loan(Origin::signed(8), kitty_ref(1)));
+        assert_eq!(Balances::free_balance(3), 15);
+        assert_eq!(Owner::<Test>::get(1), Some(3));
+
+        // the borrower can breed with the kitty as the sire.
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        assert_eq!(KittiesModule::parents(3), Some((1, 2)));
+
+        run_to_block(5);
+        assert_eq!(KittiesModule::loans(1), None);
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)));
+    });
+}
+
+#[test]
+fn lent_kitty_is_locked() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)),
+            Error::<Test>::KittyOnLoan
+        );
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(3), kitty_ref(1), Some(9)),
+            Error::<Test>::KittyOnLoan
+        );
+        assert_noop!(
+            KittiesModule::lend(Origin::signed(3), kitty_ref(1), 2, 5, 5),
+            Error::<Test>::KittyOnLoan
+        );
+    });
+}
+
+#[test]
+fn lend_a_kitty_fails_when_period_ended() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::lend(Origin::signed(3), kitty_ref(1), 8, 1, 5),
+            Error::<Test>::InvalidLoanPeriod
+        );
+    });
+}
+
+#[test]
+fn lend_a_kitty_fails_when_period_too_long() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::lend(Origin::signed(3), kitty_ref(1), 8, 22, 5),
+            Error::<Test>::LoanTooLong
+        );
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            21,
+            5
+        ));
+    });
+}
+
+#[test]
+fn cancel_loan_works_until_accepted() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::cancel_loan(Origin::signed(3), kitty_ref(1)),
+            Error::<Test>::LoanNotFound
+        );
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_noop!(
+            KittiesModule::cancel_loan(Origin::signed(8), kitty_ref(1)),
+            Error::<Test>::NotOwner
+        );
+
+        assert_ok!(KittiesModule::cancel_loan(Origin::signed(3), kitty_ref(1)));
+        assert_eq!(KittiesModule::loans(1), None);
+        assert!(crate::LoanExpiries::<Test>::get(5).is_empty());
+        assert_eq!(
+            System::events().pop().unwrap().event,
+            crate::mock::Event::KittiesModule(crate::Event::LoanCancelled { kitty_id: 1 })
+        );
+
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_ok!(KittiesModule::accept_loan(Origin::signed(8), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::cancel_loan(Origin::signed(3), kitty_ref(1)),
+            Error::<Test>::KittyOnLoan
+        );
+    });
+}
+
+#[test]
+fn accept_loan_fails_when_not_borrower() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::accept_loan(Origin::signed(8), kitty_ref(1)),
+            Error::<Test>::LoanNotFound
+        );
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_noop!(
+            KittiesModule::accept_loan(Origin::signed(2), kitty_ref(1)),
+            Error::<Test>::NotBorrower
+        );
+    });
+}
+
+#[test]
+fn swap_kitties_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(3),
+            vec![kitty_ref(1)],
+            vec![kitty_ref(2)],
+            8,
+            Some(5)
+        ));
+        assert_eq!(Balances::reserved_balance(3), 25);
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)),
+            Error::<Test>::KittyInEscrow
+        );
+
+        assert_ok!(KittiesModule::accept_swap(Origin::signed(8), 0));
+        assert_eq!(Owner::<Test>::get(1), Some(8));
+        assert_eq!(Owner::<Test>::get(2), Some(3));
+        assert_eq!(Balances::reserved_balance(3), 0);
+        assert_eq!(Balances::free_balance(8), 205);
+        assert_eq!(KittiesModule::swaps(0), None);
+    });
+}
+
+#[test]
+fn cancel_swap_releases_escrow() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(3),
+            vec![kitty_ref(1)],
+            vec![],
+            8,
+            Some(5)
+        ));
+        assert_noop!(
+            KittiesModule::cancel_swap(Origin::signed(8), 0),
+            Error::<Test>::NotSwapProposer
+        );
+
+        assert_ok!(KittiesModule::cancel_swap(Origin::signed(3), 0));
+        assert_eq!(Balances::reserved_balance(3), 20);
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)));
+    });
+}
+
+#[test]
+fn propose_swap_fails_when_not_owner() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_noop!(
+            KittiesModule::propose_swap(
+                Origin::signed(3),
+                vec![kitty_ref(2)],
+                vec![kitty_ref(1)],
+                8,
+                None
+            ),
+            Error::<Test>::NotOwner
+        );
+        assert_noop!(
+            KittiesModule::propose_swap(
+                Origin::signed(3),
+                vec![kitty_ref(1), kitty_ref(1)],
+                vec![kitty_ref(2)],
+                8,
+                None
+            ),
+            Error::<Test>::DuplicateKitty
+        );
+    });
+}
+
+#[test]
+fn accept_swap_fails_when_not_counterparty() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(3),
+            vec![kitty_ref(1)],
+            vec![],
+            8,
+            None
+        ));
+        assert_noop!(
+            KittiesModule::accept_swap(Origin::signed(2), 0),
+            Error::<Test>::NotSwapCounterparty
+        );
+    });
+}
+
+#[test]
+fn offer_and_claim_transfer_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::set_requires_acceptance(
+            Origin::signed(2),
+            true
+        ));
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)),
+            Error::<Test>::RecipientRequiresAcceptance
+        );
+
+        assert_ok!(KittiesModule::offer_transfer(
+            Origin::signed(3),
+            2,
+            kitty_ref(1)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(3));
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(3), kitty_ref(1), Some(9)),
+            Error::<Test>::KittyTransferPending
+        );
+        assert_noop!(
+            KittiesModule::claim_transfer(Origin::signed(4), kitty_ref(1)),
+            Error::<Test>::NotRecipient
+        );
+
+        assert_ok!(KittiesModule::claim_transfer(
+            Origin::signed(2),
+            kitty_ref(1)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(2));
+        assert_eq!(KittiesModule::pending_transfers(1), None);
+    });
+}
+
+#[test]
+fn reject_transfer_keeps_kitty() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::offer_transfer(
+            Origin::signed(3),
+            2,
+            kitty_ref(1)
+        ));
+
+        assert_ok!(KittiesModule::reject_transfer(
+            Origin::signed(2),
+            kitty_ref(1)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(3));
+        assert_noop!(
+            KittiesModule::claim_transfer(Origin::signed(2), kitty_ref(1)),
+            Error::<Test>::TransferNotFound
+        );
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)));
+    });
+}
+
+#[test]
+fn force_transfer_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_noop!(
+            KittiesModule::force_transfer(Origin::signed(3), 2, kitty_ref(1)),
+            sp_runtime::DispatchError::BadOrigin
+        );
+
+        assert_ok!(KittiesModule::force_transfer(
+            Origin::root(),
+            2,
+            kitty_ref(1)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(2));
+        assert_eq!(KittiesModule::loans(1), None);
+        assert_eq!(KittiesModule::kitty(1).unwrap().price, None);
+    });
+}
+
+#[test]
+fn force_burn_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+
+        assert_ok!(KittiesModule::force_burn(Origin::root(), kitty_ref(1)));
+        assert_eq!(Owner::<Test>::get(1), None);
+        assert!(!KittiesModule::frozen(1));
+        assert_eq!(Balances::reserved_balance(3), 0);
+    });
+}
+
+#[test]
+fn frozen_kitty_rejects_owner_calls() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)),
+            Error::<Test>::KittyFrozen
+        );
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(3), kitty_ref(1), Some(9)),
+            Error::<Test>::KittyFrozen
+        );
+        assert_noop!(
+            KittiesModule::burn(Origin::signed(3), kitty_ref(1)),
+            Error::<Test>::KittyFrozen
+        );
+        assert_noop!(
+            KittiesModule::lend(Origin::signed(3), kitty_ref(1), 8, 5, 5),
+            Error::<Test>::KittyFrozen
+        );
+
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)),
+            Error::<Test>::KittyNotFrozen
+        );
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)));
+
+        // what was set up before the freeze cannot be withdrawn by the owner either.
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(2),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::cancel_loan(Origin::signed(2), kitty_ref(1)),
+            Error::<Test>::KittyFrozen
+        );
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)));
+        assert_ok!(KittiesModule::cancel_loan(Origin::signed(2), kitty_ref(1)));
+
+        assert_ok!(KittiesModule::offer_transfer(
+            Origin::signed(2),
+            4,
+            kitty_ref(1)
+        ));
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::reject_transfer(Origin::signed(2), kitty_ref(1)),
+            Error::<Test>::KittyFrozen
+        );
+        // the recipient can still turn it down.
+        assert_ok!(KittiesModule::reject_transfer(
+            Origin::signed(4),
+            kitty_ref(1)
+        ));
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)));
+
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(2),
+            vec![kitty_ref(1)],
+            vec![],
+            4,
+            None
+        ));
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::cancel_swap(Origin::signed(2), 0),
+            Error::<Test>::KittyFrozen
+        );
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)));
+        assert_ok!(KittiesModule::cancel_swap(Origin::signed(2), 0));
+
+        assert_ok!(KittiesModule::list_bundle(
+            Origin::signed(2),
+            vec![kitty_ref(1)],
+            30
+        ));
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::cancel_bundle(Origin::signed(2), 0),
+            Error::<Test>::KittyFrozen
+        );
+    });
+}
+
+#[test]
+fn pause_minting_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_noop!(
+            KittiesModule::set_paused(Origin::signed(3), PauseScope::Minting),
+            sp_runtime::DispatchError::BadOrigin
+        );
+
+        assert_ok!(KittiesModule::set_paused(
+            Origin::root(),
+            PauseScope::Minting
+        ));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::PalletPaused
+        );
+        assert!(!KittiesModule::is_call_allowed(
+            &crate::Call::<Test>::create_kitty()
+        ));
+        // other scopes keep working.
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)));
+
+        assert_ok!(KittiesModule::set_paused(
+            Origin::root(),
+            PauseScope::Nothing
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+    });
+}
+
+#[test]
+fn pause_all_stops_every_user_call() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)),);
+        assert_ok!(KittiesModule::set_paused(Origin::root(), PauseScope::All));
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 2, kitty_ref(1)),
+            Error::<Test>::PalletPaused
+        );
+        assert_noop!(
+            KittiesModule::burn(Origin::signed(3), kitty_ref(1)),
+            Error::<Test>::PalletPaused
+        );
+        // privileged calls are never paused.
+        assert_ok!(KittiesModule::force_transfer(
+            Origin::root(),
+            2,
+            kitty_ref(1)
+        ));
+    });
+}
+
+#[test]
+fn set_parameters_works() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::set_parameters(Origin::signed(1), 10, 5, Some(6)),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(KittiesModule::set_parameters(
+            Origin::root(),
+            10,
+            5,
+            Some(6)
+        ));
+
+        // count 1 can afford the lowered deposit now.
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(1)),);
+        assert_eq!(Balances::reserved_balance(1), 10);
+    });
+}
+
+#[test]
+fn breed_pays_breeding_fee_to_destination() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+        assert_ok!(KittiesModule::set_parameters(
+            Origin::root(),
+            20,
+            5,
+            Some(6)
+        ));
+
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        assert_eq!(Balances::free_balance(6), 65);
+        assert_eq!(Balances::free_balance(8), 135);
+    });
+}
+
+#[test]
+fn breed_burns_breeding_fee_without_destination() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+        assert_ok!(KittiesModule::set_parameters(Origin::root(), 20, 5, None));
+        let issuance = Balances::total_issuance();
+
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(1),
+            kitty_ref(2)
+        ),);
+        assert_eq!(Balances::total_issuance(), issuance - 5);
+    });
+}
+
+#[test]
+fn create_kitty_fails_when_gen0_supply_exhausted() {
+    new_test_ext().execute_with(|| {
+        for who in 2..6 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(who)),);
+        }
+        assert_eq!(KittiesModule::gen0_supply(), 4);
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::Gen0SupplyExhausted
+        );
+
+        // bred kitties are not gen-0 and do not count against the cap.
+        set_gender(3, Gender::Male);
+        set_gender(4, Gender::Female);
+        assert_ok!(KittiesModule::transfer(Origin::signed(4), 8, kitty_ref(3)));
+        assert_ok!(KittiesModule::transfer(Origin::signed(5), 8, kitty_ref(4)));
+        assert_ok!(KittiesModule::breed(
+            Origin::signed(8),
+            kitty_ref(3),
+            kitty_ref(4)
+        ),);
+    });
+}
+
+#[test]
+fn create_kitty_is_rate_limited_per_window() {
+    new_test_ext().execute_with(|| {
+        for _ in 0..3 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        }
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::MintRateLimited
+        );
+
+        run_to_block(10);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+    });
+}
+
+#[test]
+fn mint_phases_follow_schedule() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(KittiesModule::mint_phase(), MintPhase::Public);
+        assert_noop!(
+            KittiesModule::schedule_mint_phases(Origin::root(), 5, 2),
+            Error::<Test>::InvalidMintSchedule
+        );
+        assert_ok!(KittiesModule::schedule_mint_phases(Origin::root(), 2, 5));
+        assert_eq!(KittiesModule::mint_phase(), MintPhase::Closed);
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::MintClosed
+        );
+
+        run_to_block(2);
+        assert_eq!(KittiesModule::mint_phase(), MintPhase::Presale);
+        run_to_block(5);
+        assert_eq!(KittiesModule::mint_phase(), MintPhase::Public);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+    });
+}
+
+#[test]
+fn presale_mint_requires_allowlist_and_quota() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::schedule_mint_phases(Origin::root(), 1, 5));
+        assert_ok!(KittiesModule::set_presale_terms(
+            Origin::root(),
+            1,
+            Some((5, 6))
+        ));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::NotAllowlisted
+        );
+
+        assert_ok!(KittiesModule::add_to_allowlist(Origin::root(), vec![8]));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_eq!(Balances::free_balance(6), 65);
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::PresaleQuotaExceeded
+        );
+
+        assert_ok!(KittiesModule::remove_from_allowlist(
+            Origin::root(),
+            vec![8]
+        ));
+        assert!(!KittiesModule::allowlisted(8));
+    });
+}
+
+#[test]
+fn create_kitty_in_collection_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            5,
+            Some(1),
+            true,
+            true
+        ));
+        assert_eq!(KittiesModule::next_collection_id(), 2);
+
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        assert_eq!(KittiesModule::collection_of(1), 1);
+        assert!(KittiesModule::kitties(1, 1).is_some());
+        assert_eq!(KittiesModule::collection_supply(1), 1);
+        assert_eq!(Balances::free_balance(6), 65);
+
+        assert_noop!(
+            KittiesModule::create_kitty_in_collection(Origin::signed(8), 1),
+            Error::<Test>::CollectionSupplyExhausted
+        );
+        assert_noop!(
+            KittiesModule::create_kitty_in_collection(Origin::signed(8), 2),
+            Error::<Test>::CollectionNotFound
+        );
+    });
+}
+
+#[test]
+fn collection_mints_skip_the_main_drop_but_share_the_rate_limit() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            0,
+            Some(5),
+            true,
+            true
+        ));
+        // the main drop's phases and presale do not apply to a partner collection.
+        assert_ok!(KittiesModule::schedule_mint_phases(Origin::root(), 2, 3));
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::MintClosed
+        );
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        run_to_block(2);
+        assert_noop!(
+            KittiesModule::create_kitty(Origin::signed(8)),
+            Error::<Test>::NotAllowlisted
+        );
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        assert_eq!(KittiesModule::presale_minted(8), 0);
+
+        // nor does the gen0 cap, the collection has its own supply.
+        run_to_block(3);
+        for who in 2..6 {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(who)));
+        }
+        assert_eq!(KittiesModule::gen0_supply(), 4);
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        assert_eq!(KittiesModule::gen0_supply(), 4);
+
+        // the per-account rate limit is shared.
+        assert_noop!(
+            KittiesModule::create_kitty_in_collection(Origin::signed(8), 1),
+            Error::<Test>::MintRateLimited
+        );
+    });
+}
+
+#[test]
+fn non_transferable_collection_rejects_transfers() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            0,
+            None,
+            true,
+            false
+        ));
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(8), 2, kitty_ref(1)),
+            Error::<Test>::KittySoulbound
+        );
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(8), kitty_ref(1), Some(9)),
+            Error::<Test>::KittySoulbound
+        );
+        assert_ok!(KittiesModule::burn(Origin::signed(8), kitty_ref(1)));
+    });
+}
+
+#[test]
+fn breed_fails_across_collections_or_when_disabled() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            0,
+            None,
+            false,
+            true
+        ));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)),);
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        set_gender(1, Gender::Male);
+        set_gender(2, Gender::Female);
+        set_gender(3, Gender::Male);
+
+        assert_noop!(
+            KittiesModule::breed(Origin::signed(8), kitty_ref(1), kitty_ref(2)),
+            Error::<Test>::CollectionMismatch
+        );
+        assert_noop!(
+            KittiesModule::breed(Origin::signed(8), kitty_ref(3), kitty_ref(2)),
+            Error::<Test>::BreedingDisabled
+        );
+    });
+}
+
+#[test]
+fn destroy_collection_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            0,
+            None,
+            true,
+            true
+        ));
+        assert_ok!(KittiesModule::create_kitty_in_collection(
+            Origin::signed(8),
+            1
+        ));
+        assert_noop!(
+            KittiesModule::destroy_collection(Origin::signed(8), 1),
+            Error::<Test>::NotCollectionOwner
+        );
+        assert_noop!(
+            KittiesModule::destroy_collection(Origin::signed(6), 1),
+            Error::<Test>::CollectionNotEmpty
+        );
+
+        assert_ok!(KittiesModule::burn(Origin::signed(8), kitty_ref(1)));
+        assert_ok!(KittiesModule::destroy_collection(Origin::signed(6), 1));
+        assert_eq!(KittiesModule::collections(1), None);
+    });
+}
+
+#[test]
+fn migration_moves_kitties_into_collection_zero() {
+    new_test_ext().execute_with(|| {
+        StorageVersion::<Test>::put(Releases::V1);
+        let kitty = Kitty::<Test> {
+            dna: [1u8; 16],
+            price: None,
+        };
+        put_storage_value(
+            b"KittiesModule",
+            b"Kitties",
+            &Blake2_128Concat::hash(&1u32.encode()),
+            Some(kitty.clone()),
+        );
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittiesModule::kitties(0, 1), Some(kitty));
+        assert_eq!(KittiesModule::collection_supply(0), 1);
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V5);
+    });
+}
+
+#[test]
+fn new_chain_starts_on_the_latest_layout() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V5);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittiesModule::next_kitty_id(), 2);
+        assert!(KittiesModule::kitty(1).is_some());
+    });
+}
+
+#[test]
+fn migration_backfills_deposits_and_trait_counts() {
+    new_test_ext().execute_with(|| {
+        StorageVersion::<Test>::put(Releases::V1);
+        // kitty 1 was never transferred, kitty 2 was and its deposit already released.
+        for (kitty_id, dna, owner) in [(1u32, [0x10u8; 16], 3u128), (2, [0x1f; 16], 4)].iter() {
+            let kitty = Kitty::<Test> {
+                dna: *dna,
+                price: None,
+            };
+            put_storage_value(
+                b"KittiesModule",
+                b"Kitties",
+                &Blake2_128Concat::hash(&kitty_id.encode()),
+                Some(kitty),
+            );
+            Owner::<Test>::insert(kitty_id, Some(*owner));
+        }
+        assert_ok!(Balances::reserve(&3, 20));
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittyDeposits::<Test>::get(1), Some((3, 20)));
+        assert_eq!(KittyDeposits::<Test>::get(2), None);
+        assert_eq!(KittiesModule::trait_count(0, 1), 2);
+
+        // the deposit is released on the first transfer, as it used to be.
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 5, kitty_ref(1)));
+        assert_eq!(Balances::reserved_balance(3), 0);
+    });
+}
+
+#[test]
+fn migration_splits_kitties_count_into_supply_counters() {
+    new_test_ext().execute_with(|| {
+        StorageVersion::<Test>::put(Releases::V2);
+        // three kitties were minted, two of them burned since.
+        put_storage_value(b"KittiesModule", b"KittiesCount", &[], 3u32);
+        CollectionSupply::<Test>::insert(0, 1);
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittiesModule::next_kitty_id(), 4);
+        assert_eq!(KittiesModule::total_minted(), 3);
+        assert_eq!(KittiesModule::total_burned(), 2);
+        assert_eq!(KittiesModule::live_supply(), 1);
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V5);
+    });
+}
+
+#[test]
+fn migration_records_generations_of_bred_kitties() {
+    new_test_ext().execute_with(|| {
+        StorageVersion::<Test>::put(Releases::V3);
+        // kitty 3 was bred from 1 and 2 and burned since, kitty 4 from 3 and 1.
+        Parents::<Test>::insert(4, (3, 1));
+        Parents::<Test>::insert(3, (1, 2));
+        Owner::<Test>::insert(4, Some(8));
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittiesModule::generation(4), 2);
+        assert!(!Generation::<Test>::contains_key(3));
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V5);
+    });
+}
+
+#[test]
+fn migration_indexes_native_listings() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(5)));
+        // listings made before the index existed.
+        Kitties::<Test>::mutate(0, 1, |kitty| kitty.as_mut().unwrap().price = Some(9));
+        Kitties::<Test>::mutate(0, 2, |kitty| kitty.as_mut().unwrap().price = Some(5));
+        Kitties::<Test>::mutate(0, 3, |kitty| kitty.as_mut().unwrap().price = Some(1));
+        ListingAssets::<Test>::insert(3, PaymentAsset::Asset(0));
+        StorageVersion::<Test>::put(Releases::V4);
+
+        KittiesModule::on_runtime_upgrade();
+        assert_eq!(KittiesModule::cheapest_listings(3), vec![(2, 5), (1, 9)]);
+        assert_eq!(StorageVersion::<Test>::get(), Releases::V5);
+    });
+}
+
+#[test]
+fn supply_counters_track_mints_and_burns() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(KittiesModule::next_kitty_id(), 1);
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        assert_ok!(KittiesModule::burn(Origin::signed(3), kitty_ref(1)));
+
+        assert_eq!(KittiesModule::next_kitty_id(), 3);
+        assert_eq!(KittiesModule::total_minted(), 2);
+        assert_eq!(KittiesModule::total_burned(), 1);
+        assert_eq!(KittiesModule::live_supply(), 1);
+    });
+}
+
+#[test]
+fn kitty_events_are_indexed_by_kitty_and_accounts() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        let dna = KittiesModule::kitty(1).unwrap().dna;
+        let created = System::events().pop().unwrap();
+        assert_eq!(
+            created.event,
+            crate::mock::Event::KittiesModule(crate::Event::KittyCreated {
+                owner: 3,
+                kitty_id: 1,
+                kitty_hash: KittiesModule::kitty_hash(1).unwrap(),
+                dna
+            })
+        );
+        assert_eq!(
+            created.topics,
+            vec![
+                KittiesModule::kitty_topic(1),
+                KittiesModule::account_topic(&3)
+            ]
+        );
+
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)));
+        let transfered = System::events().pop().unwrap();
+        assert_eq!(
+            transfered.topics,
+            vec![
+                KittiesModule::kitty_topic(1),
+                KittiesModule::account_topic(&3),
+                KittiesModule::account_topic(&4)
+            ]
+        );
+    });
+}
+
+#[test]
+fn price_update_reports_previous_price() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(12)
+        ));
+        assert_eq!(
+            System::events().pop().unwrap().event,
+            crate::mock::Event::KittiesModule(crate::Event::PriceUpdateForSale {
+                owner: 3,
+                kitty_id: 1,
+                previous_price: Some(9),
+                price: Some(12),
+                asset: PaymentAsset::Native
+            })
+        );
+    });
+}
+
+#[test]
+fn kitties_can_be_referenced_by_content_id() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        // same dna, creator and block, still distinct ids.
+        let hash_1 = KittiesModule::kitty_hash(1).unwrap();
+        let hash_2 = KittiesModule::kitty_hash(2).unwrap();
+        assert_ne!(hash_1, hash_2);
+        assert_eq!(KittiesModule::kitty_id_by_hash(hash_2), Some(2));
+
+        assert_ok!(KittiesModule::transfer(
+            Origin::signed(3),
+            4,
+            KittyRef::Hash(hash_2)
+        ));
+        assert_eq!(Owner::<Test>::get(2), Some(4));
+
+        assert_ok!(KittiesModule::burn(
+            Origin::signed(3),
+            KittyRef::Hash(hash_1)
+        ));
+        assert_eq!(KittiesModule::kitty_id_by_hash(hash_1), None);
+        assert_noop!(
+            KittiesModule::burn(Origin::signed(3), KittyRef::Hash(hash_1)),
+            Error::<Test>::UnknownKittyHash
+        );
+    });
+}
+
+#[test]
+fn soulbound_kitties_cannot_change_hands() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            KittiesModule::force_mint(Origin::signed(1), 1, 0, false),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        // no deposit is taken from the recipient.
+        assert_ok!(KittiesModule::force_mint(Origin::root(), 1, 0, false));
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert!(KittiesModule::soulbound(1));
+        assert_eq!(KittiesModule::gen0_supply(), 1);
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(1), 2, kitty_ref(1)),
+            Error::<Test>::KittySoulbound
+        );
+        assert_noop!(
+            KittiesModule::offer_transfer(Origin::signed(1), 2, kitty_ref(1)),
+            Error::<Test>::KittySoulbound
+        );
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(1), kitty_ref(1), Some(9)),
+            Error::<Test>::KittySoulbound
+        );
+        assert_noop!(
+            KittiesModule::propose_swap(Origin::signed(1), vec![kitty_ref(1)], vec![], 2, None),
+            Error::<Test>::KittySoulbound
+        );
+        assert_ok!(KittiesModule::burn(Origin::signed(1), kitty_ref(1)));
+        assert!(!KittiesModule::soulbound(1));
+    });
+}
+
+#[test]
+fn force_mint_can_mint_transferable_kitties() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::force_mint(Origin::root(), 1, 0, true));
+        assert!(!KittiesModule::soulbound(1));
+        assert_ok!(KittiesModule::transfer(Origin::signed(1), 2, kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::force_mint(Origin::root(), 1, 7, true),
+            Error::<Test>::CollectionNotFound
+        );
+    });
+}
+
+#[test]
+fn force_mint_counts_gen0_supply_like_other_mints() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_collection(
+            Origin::signed(6),
+            0,
+            None,
+            true,
+            true
+        ));
+        for _ in 0..4 {
+            assert_ok!(KittiesModule::force_mint(Origin::root(), 1, 0, true));
+        }
+        assert_eq!(KittiesModule::gen0_supply(), 4);
+        assert_noop!(
+            KittiesModule::force_mint(Origin::root(), 1, 0, true),
+            Error::<Test>::Gen0SupplyExhausted
+        );
+
+        // collection mints do not count towards the gen0 cap, admin mints skip the rate limit.
+        assert_ok!(KittiesModule::force_mint(Origin::root(), 1, 1, true));
+        assert_eq!(KittiesModule::gen0_supply(), 4);
+        assert_eq!(KittiesModule::mint_window_of(1), None);
+    });
+}
+
+#[test]
+fn kitty_wallet_follows_the_kitty() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        let wallet = KittiesModule::kitty_wallet(1);
+        assert_ok!(Balances::transfer(Origin::signed(8), wallet, 50));
+
+        assert_noop!(
+            KittiesModule::kitty_wallet_transfer(Origin::signed(4), kitty_ref(1), 4, 10),
+            Error::<Test>::NotOwner
+        );
+        assert_ok!(KittiesModule::kitty_wallet_transfer(
+            Origin::signed(3),
+            kitty_ref(1),
+            5,
+            10
+        ));
+        assert_eq!(Balances::free_balance(5), 60);
+
+        // the remaining funds are sold along with the kitty.
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::kitty_wallet_transfer(Origin::signed(3), kitty_ref(1), 3, 10),
+            Error::<Test>::NotOwner
+        );
+        assert_ok!(KittiesModule::kitty_wallet_transfer(
+            Origin::signed(4),
+            kitty_ref(1),
+            4,
+            10
+        ));
+        assert_eq!(Balances::free_balance(wallet), 30);
+
+        // burning sweeps the wallet to the owner.
+        assert_ok!(KittiesModule::burn(Origin::signed(4), kitty_ref(1)));
+        assert_eq!(Balances::free_balance(wallet), 0);
+        assert_eq!(Balances::free_balance(4), 40 + 10 + 30);
+    });
+}
+
+#[test]
+fn every_kitty_has_its_own_wallet() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        let wallet_1 = KittiesModule::kitty_wallet(1);
+        let wallet_2 = KittiesModule::kitty_wallet(2);
+        assert_ne!(wallet_1, wallet_2);
+        assert_ok!(Balances::transfer(Origin::signed(8), wallet_1, 50));
+        assert_ok!(Balances::transfer(Origin::signed(8), wallet_2, 70));
+
+        assert_noop!(
+            KittiesModule::kitty_wallet_transfer(Origin::signed(3), kitty_ref(1), 3, 60),
+            pallet_balances::Error::<Test>::InsufficientBalance
+        );
+        assert_ok!(KittiesModule::kitty_wallet_transfer(
+            Origin::signed(3),
+            kitty_ref(1),
+            5,
+            10
+        ));
+        assert_eq!(Balances::free_balance(wallet_1), 40);
+        assert_eq!(Balances::free_balance(wallet_2), 70);
+
+        // burning one kitty only sweeps its own wallet.
+        assert_ok!(KittiesModule::burn(Origin::signed(3), kitty_ref(1)));
+        assert_eq!(Balances::free_balance(wallet_1), 0);
+        assert_eq!(Balances::free_balance(3), 30 + 40);
+        assert_eq!(Balances::free_balance(wallet_2), 70);
+    });
+}
+
+#[test]
+fn kitty_wallet_cannot_be_spent_while_the_kitty_is_committed() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        let wallet = KittiesModule::kitty_wallet(1);
+        assert_ok!(Balances::transfer(Origin::signed(8), wallet, 50));
+        let spend = || KittiesModule::kitty_wallet_transfer(Origin::signed(8), kitty_ref(1), 5, 1);
+
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(8),
+            kitty_ref(1),
+            3,
+            5,
+            5
+        ));
+        assert_noop!(spend(), Error::<Test>::KittyOnLoan);
+        assert_ok!(KittiesModule::cancel_loan(Origin::signed(8), kitty_ref(1)));
+
+        assert_ok!(KittiesModule::offer_transfer(
+            Origin::signed(8),
+            3,
+            kitty_ref(1)
+        ));
+        assert_noop!(spend(), Error::<Test>::KittyTransferPending);
+        assert_ok!(KittiesModule::reject_transfer(
+            Origin::signed(8),
+            kitty_ref(1)
+        ));
+
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(8),
+            vec![kitty_ref(1)],
+            vec![],
+            3,
+            None
+        ));
+        assert_noop!(spend(), Error::<Test>::KittyInEscrow);
+        assert_ok!(KittiesModule::cancel_swap(Origin::signed(8), 0));
+
+        assert_ok!(KittiesModule::list_bundle(
+            Origin::signed(8),
+            vec![kitty_ref(1)],
+            30
+        ));
+        assert_noop!(spend(), Error::<Test>::KittyInBundle);
+        assert_ok!(KittiesModule::cancel_bundle(Origin::signed(8), 0));
+
+        assert_ok!(<KittiesModule as KittyLock<u32>>::set_lock(1, *b"staking "));
+        assert_noop!(spend(), Error::<Test>::KittyLocked);
+        <KittiesModule as KittyLock<u32>>::remove_lock(1, *b"staking ");
+
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(1)));
+        assert_noop!(spend(), Error::<Test>::KittyFrozen);
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(1)));
+
+        assert_ok!(spend());
+        assert_eq!(Balances::free_balance(wallet), 49);
+    });
+}
+
+#[test]
+fn spending_from_the_kitty_wallet_ends_its_listing() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        let wallet = KittiesModule::kitty_wallet(1);
+        assert_ok!(Balances::transfer(Origin::signed(8), wallet, 50));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(8),
+            kitty_ref(1),
+            Some(40)
+        ));
+
+        assert_ok!(KittiesModule::kitty_wallet_transfer(
+            Origin::signed(8),
+            kitty_ref(1),
+            8,
+            50
+        ));
+        assert_eq!(KittiesModule::kitty(1).unwrap().price, None);
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![]);
+        assert_noop!(
+            KittiesModule::buy_kitty(Origin::signed(6), kitty_ref(1), PaymentAsset::Native, 40),
+            Error::<Test>::KittyNotForSale
+        );
+    });
+}
+
+#[test]
+fn lifecycle_hooks_see_mints_transfers_and_burns() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)));
+        assert_ok!(KittiesModule::force_transfer(
+            Origin::root(),
+            5,
+            kitty_ref(1)
+        ));
+        assert_ok!(KittiesModule::burn(Origin::signed(5), kitty_ref(1)));
+        assert_eq!(
+            kitty_changes(),
+            vec![
+                KittyChange::Minted(3, 1),
+                KittyChange::Transferred(3, 4, 1),
+                KittyChange::Transferred(4, 5, 1),
+                KittyChange::Burned(5, 1),
+            ]
+        );
+    });
+}
+
+#[test]
+fn locked_kitties_cannot_move() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_noop!(
+            <KittiesModule as KittyLock<u32>>::set_lock(2, *b"staking "),
+            Error::<Test>::InvalidKittyIndex
+        );
+        assert_ok!(<KittiesModule as KittyLock<u32>>::set_lock(1, *b"staking "));
+        assert_ok!(<KittiesModule as KittyLock<u32>>::set_lock(1, *b"auction "));
+        assert_ok!(<KittiesModule as KittyLock<u32>>::set_lock(1, *b"staking "));
+        assert_eq!(
+            KittiesModule::kitty_locks(1),
+            vec![*b"auction ", *b"staking "]
+        );
+
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)),
+            Error::<Test>::KittyLocked
+        );
+        assert_noop!(
+            KittiesModule::sell_kitty(Origin::signed(3), kitty_ref(1), Some(9)),
+            Error::<Test>::KittyLocked
+        );
+        assert_noop!(
+            KittiesModule::propose_swap(Origin::signed(3), vec![kitty_ref(1)], vec![], 4, None),
+            Error::<Test>::KittyLocked
+        );
+        assert_noop!(
+            KittiesModule::burn(Origin::signed(3), kitty_ref(1)),
+            Error::<Test>::KittyLocked
+        );
+        assert_noop!(
+            KittiesModule::force_transfer(Origin::root(), 4, kitty_ref(1)),
+            Error::<Test>::KittyLocked
+        );
+        assert_noop!(
+            KittiesModule::force_burn(Origin::root(), kitty_ref(1)),
+            Error::<Test>::KittyLocked
+        );
+
+        <KittiesModule as KittyLock<u32>>::remove_lock(1, *b"staking ");
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)),
+            Error::<Test>::KittyLocked
+        );
+        <KittiesModule as KittyLock<u32>>::remove_lock(1, *b"auction ");
+        assert!(!KittyLocks::<Test>::contains_key(1));
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)));
+    });
+}
+
+#[test]
+fn provenance_keeps_the_latest_owners() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 4, kitty_ref(1)));
+
+        run_to_block(2);
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(4),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(5),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            9
+        ));
+        assert_eq!(
+            KittiesModule::provenance(1),
+            vec![
+                ProvenanceRecord {
+                    previous_owner: 3,
+                    block: 1,
+                    sale_price: None
+                },
+                ProvenanceRecord {
+                    previous_owner: 4,
+                    block: 2,
+                    sale_price: Some((9, PaymentAsset::Native))
+                },
+            ]
+        );
+
+        // only `MaxProvenance` records are kept.
+        run_to_block(3);
+        assert_ok!(KittiesModule::force_transfer(
+            Origin::root(),
+            6,
+            kitty_ref(1)
+        ));
+        let provenance = KittiesModule::provenance(1);
+        assert_eq!(provenance.len(), 2);
+        assert_eq!(provenance[0].previous_owner, 4);
+        assert_eq!(provenance[1].previous_owner, 5);
+        assert_eq!(provenance[1].block, 3);
+    });
+}
+
+// overwrite the dna of a kitty so its dominant trait is known.
+fn set_dna(kitty_id: u32, dna: [u8; 16]) {
+    Kitties::<Test>::mutate(KittiesModule::collection_of(kitty_id), kitty_id, |kitty| {
+        if let Some(kitty) = kitty {
+            kitty.dna = dna;
+        }
+    });
+}
+
+#[test]
+fn sales_update_last_price_volume_and_floors() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        set_dna(1, [0x30; 16]);
+        set_dna(2, [0x31; 16]);
+
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(9)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(5),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            9
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(4),
+            kitty_ref(2),
+            Some(7)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(6),
+            kitty_ref(2),
+            PaymentAsset::Native,
+            7
+        ));
+
+        assert_eq!(KittiesModule::last_sale_price(1), Some(9));
+        assert_eq!(KittiesModule::rolling_volume(), 16);
+        assert_eq!(KittiesModule::generation_floor(0), Some((0, 7)));
+        assert_eq!(KittiesModule::trait_floor(3), Some((0, 7)));
+
+        // floors restart with every window, the volume keeps the previous window.
+        run_to_block(10);
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(5),
+            kitty_ref(1),
+            Some(12)
+        ));
+        assert_ok!(KittiesModule::buy_kitty(
+            Origin::signed(4),
+            kitty_ref(1),
+            PaymentAsset::Native,
+            12
+        ));
+        assert_eq!(KittiesModule::last_sale_price(1), Some(12));
+        assert_eq!(KittiesModule::rolling_volume(), 28);
+        assert_eq!(KittiesModule::generation_floor(0), Some((10, 12)));
+        assert_eq!(KittiesModule::trait_floor(3), Some((10, 12)));
+
+        run_to_block(20);
+        assert_eq!(KittiesModule::rolling_volume(), 12);
+        run_to_block(30);
+        assert_eq!(KittiesModule::rolling_volume(), 0);
+    });
+}
+
+#[test]
+fn dominant_trait_prefers_the_lowest_variant_on_a_tie() {
+    let mut dna = [0x50; 16];
+    dna[..8].copy_from_slice(&[0x2f; 8]);
+    assert_eq!(KittiesModule::dominant_trait(&dna), 2);
+}
+
+#[test]
+fn listings_are_indexed_by_price() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(5)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(300)
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(4),
+            kitty_ref(2),
+            Some(5)
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(5),
+            kitty_ref(3),
+            Some(7)
+        ));
+        assert_eq!(KittiesModule::cheapest_listings(2), vec![(2, 5), (3, 7)]);
+
+        // repricing moves a listing, a listing in another asset leaves the index.
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(6)
+        ));
+        assert_ok!(KittiesModule::sell_kitty_for_asset(
+            Origin::signed(4),
+            kitty_ref(2),
+            Some(1),
+            PaymentAsset::Asset(0)
+        ));
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![(1, 6), (3, 7)]);
+
+        // a transfer ends the listing of the previous owner.
+        assert_ok!(KittiesModule::transfer(Origin::signed(3), 6, kitty_ref(1)));
+        assert_eq!(KittiesModule::kitty(1).unwrap().price, None);
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![(3, 7)]);
+    });
+}
+
+#[test]
+fn encumbered_kitties_leave_the_listing_index() {
+    new_test_ext().execute_with(|| {
+        for (who, kitty_id) in vec![(3, 1), (4, 2), (5, 3), (6, 4)] {
+            assert_ok!(KittiesModule::create_kitty(Origin::signed(who)));
+            assert_ok!(KittiesModule::sell_kitty(
+                Origin::signed(who),
+                kitty_ref(kitty_id),
+                Some(kitty_id.into())
+            ));
+        }
+        assert_eq!(
+            KittiesModule::cheapest_listings(5),
+            vec![(1, 1), (2, 2), (3, 3), (4, 4)]
+        );
+
+        assert_ok!(KittiesModule::lend(
+            Origin::signed(3),
+            kitty_ref(1),
+            8,
+            5,
+            5
+        ));
+        assert_ok!(KittiesModule::offer_transfer(
+            Origin::signed(4),
+            8,
+            kitty_ref(2)
+        ));
+        assert_ok!(KittiesModule::propose_swap(
+            Origin::signed(5),
+            vec![kitty_ref(3)],
+            vec![],
+            8,
+            None
+        ));
+        assert_ok!(KittiesModule::freeze_kitty(Origin::root(), kitty_ref(4)));
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![]);
+        for kitty_id in 1..=4 {
+            assert_eq!(KittiesModule::kitty(kitty_id).unwrap().price, None);
+        }
+
+        assert_ok!(KittiesModule::thaw_kitty(Origin::root(), kitty_ref(4)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(6),
+            kitty_ref(4),
+            Some(4)
+        ));
+        assert_ok!(<KittiesModule as KittyLock<u32>>::set_lock(4, *b"staking "));
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![]);
+        assert_noop!(
+            KittiesModule::buy_cheapest(Origin::signed(8), 10),
+            Error::<Test>::NoListingWithinPrice
+        );
+    });
+}
+
+#[test]
+fn buy_cheapest_works() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(4)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(5)
+        ));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(4),
+            kitty_ref(2),
+            Some(8)
+        ));
+
+        assert_noop!(
+            KittiesModule::buy_cheapest(Origin::signed(6), 4),
+            Error::<Test>::NoListingWithinPrice
+        );
+        // the seller's own cheaper listing is skipped.
+        assert_ok!(KittiesModule::buy_cheapest(Origin::signed(3), 8));
+        assert_eq!(Owner::<Test>::get(2), Some(3));
+        assert_ok!(KittiesModule::buy_cheapest(Origin::signed(6), 8));
+        assert_eq!(Owner::<Test>::get(1), Some(6));
+        assert_eq!(KittiesModule::cheapest_listings(5), vec![]);
+    });
+}
+
+#[test]
+fn buy_cheapest_gives_up_after_a_bounded_scan() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(3),
+            kitty_ref(1),
+            Some(5)
+        ));
+        // the buyer's own cheaper listings fill the whole scan.
+        for kitty_id in 100..100 + MAX_CHEAPEST_SCAN as u32 {
+            Owner::<Test>::insert(kitty_id, Some(6));
+            ListingIndex::<Test>::insert(1u128.to_be_bytes(), kitty_id, 1);
+        }
+        assert_noop!(
+            KittiesModule::buy_cheapest(Origin::signed(6), 5),
+            Error::<Test>::NoListingWithinPrice
+        );
+
+        ListingIndex::<Test>::remove(1u128.to_be_bytes(), 100);
+        assert_ok!(KittiesModule::buy_cheapest(Origin::signed(6), 5));
+        assert_eq!(Owner::<Test>::get(1), Some(6));
+    });
+}
+
+#[test]
+fn buy_bundle_moves_every_kitty() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        assert_ok!(KittiesModule::sell_kitty(
+            Origin::signed(8),
+            kitty_ref(1),
+            Some(5)
+        ));
+        assert_ok!(KittiesModule::list_bundle(
+            Origin::signed(8),
+            vec![kitty_ref(1), kitty_ref(2)],
+            31
+        ));
+        assert_eq!(KittiesModule::bundle_of(1), Some(0));
+        // bundled kitties are only sold together.
+        assert_eq!(KittiesModule::kitty(1).unwrap().price, None);
+        assert_noop!(
+            KittiesModule::transfer(Origin::signed(8), 3, kitty_ref(2)),
+            Error::<Test>::KittyInBundle
+        );
+
+        assert_noop!(
+            KittiesModule::buy_bundle(Origin::signed(6), 0, 30),
+            Error::<Test>::BundlePriceTooHigh
+        );
+        assert_ok!(KittiesModule::buy_bundle(Origin::signed(6), 0, 31));
+        assert_eq!(Owner::<Test>::get(1), Some(6));
+        assert_eq!(Owner::<Test>::get(2), Some(6));
+        assert_eq!(Balances::free_balance(6), 29);
+        // the bundle price counts towards the volume, but no kitty sold at a price of its own.
+        assert_eq!(KittiesModule::rolling_volume(), 31);
+        assert_eq!(KittiesModule::last_sale_price(1), None);
+        assert_eq!(KittiesModule::last_sale_price(2), None);
+        assert_eq!(GenerationFloor::<Test>::iter().count(), 0);
+        assert_eq!(TraitFloor::<Test>::iter().count(), 0);
+        // provenance splits the price evenly, the last kitty takes the remainder.
+        assert_eq!(
+            KittiesModule::provenance(1)[0].sale_price,
+            Some((15, PaymentAsset::Native))
+        );
+        assert_eq!(
+            KittiesModule::provenance(2)[0].sale_price,
+            Some((16, PaymentAsset::Native))
+        );
+        assert_eq!(KittiesModule::bundles(0), None);
+        assert_eq!(KittiesModule::bundle_of(1), None);
+    });
+}
+
+#[test]
+fn cancel_bundle_unlocks_its_kitties() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(8)));
+        assert_noop!(
+            KittiesModule::list_bundle(Origin::signed(8), vec![kitty_ref(1), kitty_ref(1)], 30),
+            Error::<Test>::DuplicateKitty
+        );
+        assert_ok!(KittiesModule::list_bundle(
+            Origin::signed(8),
+            vec![kitty_ref(1), kitty_ref(2)],
+            30
+        ));
+        assert_noop!(
+            KittiesModule::cancel_bundle(Origin::signed(6), 0),
+            Error::<Test>::NotBundleSeller
+        );
+
+        assert_ok!(KittiesModule::cancel_bundle(Origin::signed(8), 0));
+        assert_eq!(KittiesModule::bundle_of(1), None);
+        assert_noop!(
+            KittiesModule::buy_bundle(Origin::signed(6), 0, 30),
+            Error::<Test>::BundleNotFound
+        );
+        assert_ok!(KittiesModule::transfer(Origin::signed(8), 3, kitty_ref(2)));
+    });
+}
+
+// what the seller's key produces for an order in the mock, `TestSignature` keeps the message.
+fn sign_order(seller: u128, order: &SellOrderOf<Test>) -> TestSignature {
+    TestSignature(
+        seller,
+        (SELL_ORDER_CONTEXT, System::block_hash(0), order).encode(),
+    )
+}
+
+#[test]
+fn fill_order_settles_a_signed_order() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        let order = SellOrder {
+            kitty_id: 1,
+            price: 9,
+            expiry: 5,
+            nonce: 7,
+        };
+
+        // signed by someone else than the seller.
+        assert_noop!(
+            KittiesModule::fill_order(Origin::signed(5), 3, order.clone(), sign_order(4, &order)),
+            Error::<Test>::InvalidSignature
+        );
+        // signed for a chain with another genesis block.
+        let other_chain = (SELL_ORDER_CONTEXT, sp_core::H256::repeat_byte(1), &order).encode();
+        assert_noop!(
+            KittiesModule::fill_order(
+                Origin::signed(5),
+                3,
+                order.clone(),
+                TestSignature(3, other_chain)
+            ),
+            Error::<Test>::InvalidSignature
+        );
+        assert_ok!(KittiesModule::fill_order(
+            Origin::signed(5),
+            3,
+            order.clone(),
+            sign_order(3, &order)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(5));
+        assert_eq!(Balances::free_balance(3), 30 + 9);
+        assert_eq!(KittiesModule::last_sale_price(1), Some(9));
+        assert!(KittiesModule::order_nonce_used(3, 7));
+        assert_eq!(
+            System::events().pop().unwrap().event,
+            crate::mock::Event::KittiesModule(crate::Event::OrderFilled {
+                seller: 3,
+                nonce: 7
+            })
+        );
+
+        // the same order cannot be filled twice, even once the seller owns the kitty again.
+        assert_ok!(KittiesModule::transfer(Origin::signed(5), 3, kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::fill_order(Origin::signed(6), 3, order.clone(), sign_order(3, &order)),
+            Error::<Test>::OrderNonceUsed
+        );
+    });
+}
+
+#[test]
+fn expired_or_cancelled_orders_cannot_be_filled() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        let order = SellOrder {
+            kitty_id: 1,
+            price: 9,
+            expiry: 5,
+            nonce: 0,
+        };
+
+        assert_ok!(KittiesModule::cancel_order_nonce(Origin::signed(3), 0));
+        assert_noop!(
+            KittiesModule::cancel_order_nonce(Origin::signed(3), 0),
+            Error::<Test>::OrderNonceUsed
+        );
+        assert_noop!(
+            KittiesModule::fill_order(Origin::signed(5), 3, order.clone(), sign_order(3, &order)),
+            Error::<Test>::OrderNonceUsed
+        );
+
+        let order = SellOrder { nonce: 1, ..order };
+        run_to_block(6);
+        assert_noop!(
+            KittiesModule::fill_order(Origin::signed(5), 3, order.clone(), sign_order(3, &order)),
+            Error::<Test>::OrderExpired
+        );
+    });
+}
+
+// what the owner's key produces for a relayed transfer in the mock.
+fn sign_transfer(owner: u128, payload: &RelayedTransferOf<Test>) -> TestSignature {
+    TestSignature(
+        owner,
+        (RELAYED_TRANSFER_CONTEXT, System::block_hash(0), payload).encode(),
+    )
+}
+
+#[test]
+fn relay_transfer_moves_a_kitty_for_its_owner() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(KittiesModule::create_kitty(Origin::signed(3)));
+        let payload = RelayedTransfer {
+            kitty_id: 1,
+            to: 4,
+            nonce: 0,
+            deadline: 5,
+        };
+
+        assert_noop!(
+            KittiesModule::relay_transfer(
+                Origin::signed(8),
+                payload.clone(),
+                sign_transfer(4, &payload)
+            ),
+            Error::<Test>::InvalidSignature
+        );
+        // signed for a chain with another genesis block.
+        let other_genesis = sp_core::H256::repeat_byte(1);
+        let other_chain = (RELAYED_TRANSFER_CONTEXT, other_genesis, &payload).encode();
+        assert_noop!(
+            KittiesModule::relay_transfer(
+                Origin::signed(8),
+                payload.clone(),
+                TestSignature(3, other_chain)
+            ),
+            Error::<Test>::InvalidSignature
+        );
+        assert_ok!(KittiesModule::relay_transfer(
+            Origin::signed(8),
+            payload.clone(),
+            sign_transfer(3, &payload)
+        ));
+        assert_eq!(Owner::<Test>::get(1), Some(4));
+        assert_eq!(KittiesModule::relay_nonce(3), 1);
+
+        // replaying the signature fails once the kitty is back with its owner.
+        assert_ok!(KittiesModule::transfer(Origin::signed(4), 3, kitty_ref(1)));
+        assert_noop!(
+            KittiesModule::relay_transfer(
+                Origin::signed(8),
+                payload.clone(),
+                sign_transfer(3, &payload)
+            ),
+            Error::<Test>::InvalidRelayNonce
+        );
+
+        let payload = RelayedTransfer {
+            nonce: 1,
+            ..payload
+        };
+        run_to_block(6);
+        assert_noop!(
+            KittiesModule::relay_transfer(
+                Origin::signed(8),
+                payload.clone(),
+                sign_transfer(3, &payload)
+            ),
+            Error::<Test>::RelayDeadlinePassed
+        );
+    });
+}
@@ -0,0 +1,45 @@
+//! Extension points for other pallets that build on kitties.
+
+use frame_support::{dispatch::DispatchResult, sp_std::prelude::Vec};
+
+/// Hooks called whenever a kitty is minted, changes owner or is burned, so other pallets can
+/// keep their own state in line with kitty ownership.
+pub trait KittyLifecycleHooks<AccountId, KittyIndex> {
+    /// A kitty was minted to `owner`.
+    fn on_mint(owner: &AccountId, kitty_id: KittyIndex);
+    /// A kitty moved from `from` to `to`.
+    fn on_transfer(from: &AccountId, to: &AccountId, kitty_id: KittyIndex);
+    /// A kitty owned by `owner` was burned.
+    fn on_burn(owner: &AccountId, kitty_id: KittyIndex);
+}
+
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+impl<AccountId, KittyIndex: Copy> KittyLifecycleHooks<AccountId, KittyIndex> for Tuple {
+    fn on_mint(owner: &AccountId, kitty_id: KittyIndex) {
+        for_tuples!( #( Tuple::on_mint(owner, kitty_id); )* );
+    }
+
+    fn on_transfer(from: &AccountId, to: &AccountId, kitty_id: KittyIndex) {
+        for_tuples!( #( Tuple::on_transfer(from, to, kitty_id); )* );
+    }
+
+    fn on_burn(owner: &AccountId, kitty_id: KittyIndex) {
+        for_tuples!( #( Tuple::on_burn(owner, kitty_id); )* );
+    }
+}
+
+/// Identifies who holds a kitty lock, e.g. `*b"auction "`.
+pub type LockReason = [u8; 8];
+
+/// Lets other pallets pin a kitty in place while they hold it. A kitty with any lock cannot be
+/// transferred, sold, swapped or burned until every reason is removed, and this holds for the
+/// privileged `force_transfer` and `force_burn` calls too, so a lock holder never sees a kitty
+/// move or disappear underneath it.
+pub trait KittyLock<KittyIndex> {
+    /// Add `reason` to the kitty's locks, a no-op if it is already set.
+    fn set_lock(kitty_id: KittyIndex, reason: LockReason) -> DispatchResult;
+    /// Remove `reason` from the kitty's locks, a no-op if it is not set.
+    fn remove_lock(kitty_id: KittyIndex, reason: LockReason);
+    /// The reasons currently locking the kitty.
+    fn locks(kitty_id: KittyIndex) -> Vec<LockReason>;
+}
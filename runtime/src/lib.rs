@@ -6,18 +6,23 @@
 #[cfg(feature = "std")]
 include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
+use codec::{Decode, Encode};
 use pallet_grandpa::fg_primitives;
 use pallet_grandpa::{AuthorityId as GrandpaId, AuthorityList as GrandpaAuthorityList};
 use sp_api::impl_runtime_apis;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::traits::{
-    AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, Verify,
+    AccountIdLookup, BlakeTwo256, Block as BlockT, DispatchInfoOf, IdentifyAccount, NumberFor,
+    SignedExtension, Verify,
 };
 use sp_runtime::{
     create_runtime_str, generic, impl_opaque_keys,
-    transaction_validity::{TransactionSource, TransactionValidity},
-    ApplyExtrinsicResult, MultiSignature,
+    transaction_validity::{
+        InvalidTransaction, TransactionSource, TransactionValidity, TransactionValidityError,
+        ValidTransaction,
+    },
+    ApplyExtrinsicResult, MultiSignature, RuntimeDebug,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -32,7 +37,7 @@ pub use frame_support::{
         constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
         IdentityFee, Weight,
     },
-    StorageValue,
+    PalletId, StorageValue,
 };
 pub use pallet_balances::Call as BalancesCall;
 pub use pallet_timestamp::Call as TimestampCall;
@@ -63,6 +68,12 @@ pub type Balance = u128;
 /// Index of a transaction in the chain.
 pub type Index = u32;
 
+/// Index of a kitty.
+pub type KittyIndex = u32;
+
+/// Identifier of a fungible asset in `pallet_assets`.
+pub type AssetId = u32;
+
 /// A hash of some data used by the chain.
 pub type Hash = sp_core::H256;
 
@@ -102,10 +113,10 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     //   `spec_version`, and `authoring_version` are the same between Wasm and native.
     // This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
     //   the compatible custom types.
-    spec_version: 100,
+    spec_version: 102,
     impl_version: 1,
     apis: RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    transaction_version: 3,
 };
 
 /// This determines the average expected block time that we are targeting.
@@ -256,6 +267,30 @@ impl pallet_balances::Config for Runtime {
     type WeightInfo = pallet_balances::weights::SubstrateWeight<Runtime>;
 }
 
+parameter_types! {
+    pub const AssetDeposit: Balance = 100 * ExistentialDeposit::get();
+    pub const ApprovalDeposit: Balance = ExistentialDeposit::get();
+    pub const StringLimit: u32 = 50;
+    pub const MetadataDepositBase: Balance = 10 * ExistentialDeposit::get();
+    pub const MetadataDepositPerByte: Balance = ExistentialDeposit::get();
+}
+
+impl pallet_assets::Config for Runtime {
+    type Event = Event;
+    type Balance = Balance;
+    type AssetId = AssetId;
+    type Currency = Balances;
+    type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+    type AssetDeposit = AssetDeposit;
+    type MetadataDepositBase = MetadataDepositBase;
+    type MetadataDepositPerByte = MetadataDepositPerByte;
+    type ApprovalDeposit = ApprovalDeposit;
+    type StringLimit = StringLimit;
+    type Freezer = ();
+    type Extra = ();
+    type WeightInfo = pallet_assets::weights::SubstrateWeight<Runtime>;
+}
+
 parameter_types! {
     pub const TransactionByteFee: Balance = 1;
 }
@@ -282,12 +317,38 @@ impl pallet_kitties::Config for Runtime {
     type Event = Event;
     type Randomness = RandomnessCollectiveFlip;
     type Currency = Balances;
-    type KittyIndex = u32;
+    type KittyIndex = KittyIndex;
     type MintDeposit = MintDepositAmount;
+    type MaxSwapKitties = MaxSwapKitties;
+    type ForceOrigin = frame_system::EnsureRoot<AccountId>;
+    type PauseOrigin = frame_system::EnsureRoot<AccountId>;
+    type ParametersOrigin = frame_system::EnsureRoot<AccountId>;
+    type MaxGen0Supply = MaxGen0Supply;
+    type MintWindow = MintWindow;
+    type MaxMintsPerWindow = MaxMintsPerWindow;
+    type MintAdminOrigin = frame_system::EnsureRoot<AccountId>;
+    type Assets = Assets;
+    type PalletId = KittiesPalletId;
+    type OnKittyChange = ();
+    type MaxProvenance = MaxProvenance;
+    type VolumeWindow = VolumeWindow;
+    type MaxBundleKitties = MaxBundleKitties;
+    type OffchainSignature = Signature;
+    type OffchainPublic = <Signature as Verify>::Signer;
+    type MaxLoanPeriod = MaxLoanPeriod;
 }
 
 parameter_types! {
   pub const MintDepositAmount: Balance = 8;
+  pub const MaxSwapKitties: u32 = 16;
+  pub const MaxGen0Supply: u32 = 50_000;
+  pub const MintWindow: BlockNumber = HOURS;
+  pub const MaxMintsPerWindow: u32 = 10;
+  pub const KittiesPalletId: PalletId = PalletId(*b"py/kitty");
+  pub const MaxProvenance: u32 = 32;
+  pub const VolumeWindow: BlockNumber = DAYS;
+  pub const MaxBundleKitties: u32 = 16;
+  pub const MaxLoanPeriod: BlockNumber = 30 * DAYS;
 }
 // Create the runtime by composing the FRAME pallets that were previously configured.
 construct_runtime!(
@@ -302,11 +363,12 @@ construct_runtime!(
         Aura: pallet_aura::{Pallet, Config<T>},
         Grandpa: pallet_grandpa::{Pallet, Call, Storage, Config, Event},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+        Assets: pallet_assets::{Pallet, Call, Storage, Event<T>},
         TransactionPayment: pallet_transaction_payment::{Pallet, Storage},
         Sudo: pallet_sudo::{Pallet, Call, Config<T>, Storage, Event<T>},
         // Include the custom logic from the pallet-template in the runtime.
         TemplateModule: pallet_template::{Pallet, Call, Storage, Event<T>},
-        KittiesModule: pallet_kitties::{Pallet, Call, Storage, Event<T>},
+        KittiesModule: pallet_kitties::{Pallet, Call, Config, Storage, Event<T>},
     }
 );
 
@@ -325,7 +387,40 @@ pub type SignedExtra = (
     frame_system::CheckNonce<Runtime>,
     frame_system::CheckWeight<Runtime>,
     pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+    CheckKittiesPause,
 );
+
+/// Rejects the calls of the kitties pallet that its pause switch currently stops while the
+/// transaction is validated, so they never enter the pool and are never charged a fee.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Default, RuntimeDebug)]
+pub struct CheckKittiesPause;
+
+impl SignedExtension for CheckKittiesPause {
+    const IDENTIFIER: &'static str = "CheckKittiesPause";
+    type AccountId = AccountId;
+    type Call = Call;
+    type AdditionalSigned = ();
+    type Pre = ();
+
+    fn additional_signed(&self) -> Result<(), TransactionValidityError> {
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        _who: &Self::AccountId,
+        call: &Self::Call,
+        _info: &DispatchInfoOf<Self::Call>,
+        _len: usize,
+    ) -> TransactionValidity {
+        match call {
+            Call::KittiesModule(call) if !KittiesModule::is_call_allowed(call) => {
+                Err(InvalidTransaction::Call.into())
+            }
+            _ => Ok(ValidTransaction::default()),
+        }
+    }
+}
 /// Unchecked extrinsic type as expected by this runtime.
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
 /// Executive: handles dispatch to the various modules.
@@ -463,6 +558,61 @@ impl_runtime_apis! {
         }
     }
 
+    impl pallet_kitties_runtime_api::KittiesApi<
+        Block,
+        KittyIndex,
+        Balance,
+        pallet_kitties::ProvenanceRecordOf<Runtime>,
+    > for Runtime {
+        fn rarity_of(kitty_id: KittyIndex) -> Option<u64> {
+            KittiesModule::rarity_of(kitty_id)
+        }
+
+        fn next_kitty_id() -> KittyIndex {
+            KittiesModule::next_kitty_id()
+        }
+
+        fn total_minted() -> KittyIndex {
+            KittiesModule::total_minted()
+        }
+
+        fn total_burned() -> KittyIndex {
+            KittiesModule::total_burned()
+        }
+
+        fn live_supply() -> KittyIndex {
+            KittiesModule::live_supply()
+        }
+
+        fn locks_of(kitty_id: KittyIndex) -> Vec<[u8; 8]> {
+            KittiesModule::kitty_locks(kitty_id)
+        }
+
+        fn provenance_of(kitty_id: KittyIndex) -> Vec<pallet_kitties::ProvenanceRecordOf<Runtime>> {
+            KittiesModule::provenance(kitty_id)
+        }
+
+        fn last_sale_price(kitty_id: KittyIndex) -> Option<Balance> {
+            KittiesModule::last_sale_price(kitty_id)
+        }
+
+        fn rolling_volume() -> Balance {
+            KittiesModule::rolling_volume()
+        }
+
+        fn generation_floor(generation: u32) -> Option<Balance> {
+            KittiesModule::generation_floor(generation).map(|(_, price)| price)
+        }
+
+        fn trait_floor(variant: u8) -> Option<Balance> {
+            KittiesModule::trait_floor(variant).map(|(_, price)| price)
+        }
+
+        fn cheapest_listings(n: u32) -> Vec<(KittyIndex, Balance)> {
+            KittiesModule::cheapest_listings(n)
+        }
+    }
+
     #[cfg(feature = "runtime-benchmarks")]
     impl frame_benchmarking::Benchmark<Block> for Runtime {
         fn dispatch_benchmark(